@@ -1,8 +1,8 @@
 pub(super) fn run() -> Result<(), super::Error> {
-	let adapters = parse_adapters(super::read_input_lines::<u64>("day10")?)?;
+	let adapters = Adapters::parse(super::read_input_lines::<u64>("day10")?, 3)?;
 
 	{
-		let result = part1(&adapters)?;
+		let result = part1(&adapters);
 
 		println!("10a: {result}");
 
@@ -10,60 +10,246 @@ pub(super) fn run() -> Result<(), super::Error> {
 	}
 
 	{
-		let result = part2(&adapters);
+		let result = part2(&adapters)?;
 
 		println!("10b: {result}");
 
 		assert_eq!(result, 64793042714624);
+
+		// The first arrangement uses every adapter, and enumerating arrangements agrees with indexing them.
+		let mut arrangements = adapters.arrangements();
+		assert_eq!(arrangements.next().as_deref(), Some(&adapters.joltages[..]));
+		assert_eq!(arrangements.next(), adapters.arrangement(&1_u8.into()));
+		assert_eq!(adapters.arrangement(&result.into()), None);
+
+		// Every arrangement is a combination of independent arrangements of each segment.
+		let segments = adapters.segments();
+		let num_arrangements: num_bigint::BigUint = segments.iter().map(|segment| &segment.num_arrangements).product();
+		assert_eq!(num_arrangements, result.into());
+		assert_eq!(
+			segments.iter().map(|segment| segment.joltages.len() - 2).sum::<usize>(),
+			adapters.optional_adapters().len(),
+		);
+		assert_eq!(adapters.mandatory_adapters().len() + adapters.optional_adapters().len(), adapters.joltages.len() - 2);
 	}
 
 	Ok(())
 }
 
-fn parse_adapters(input: impl Iterator<Item = Result<u64, super::Error>>) -> Result<Vec<u64>, super::Error> {
-	let adapters: Result<Vec<_>, _> = std::iter::once(Ok(0)).chain(input).collect();
-	let mut adapters = adapters?;
+/// The joltages of a chain of adapters sorted in ascending order, starting with the charging outlet and ending with the device.
+#[derive(Debug)]
+struct Adapters {
+	joltages: Vec<u64>,
+
+	/// The largest difference in joltage that an adapter can take.
+	max_gap: u64,
+}
+
+impl Adapters {
+	fn parse(input: impl Iterator<Item = Result<u64, super::Error>>, max_gap: u64) -> Result<Self, super::Error> {
+		let joltages: Result<Vec<_>, _> = std::iter::once(Ok(0)).chain(input).collect();
+		let mut joltages = joltages?;
+		if joltages.len() == 1 {
+			return Err("no adapters found in input".into());
+		}
+
+		joltages.sort_unstable();
+
+		let device = joltages.last().expect("joltages is not empty").checked_add(max_gap).ok_or("device joltage overflow")?;
+		joltages.push(device);
+
+		for pair in joltages.windows(2) {
+			if pair[1] - pair[0] > max_gap {
+				return Err(format!("adapters cannot be chained: {} jolts to {} jolts is a gap of more than {max_gap} jolts", pair[0], pair[1]).into());
+			}
+		}
+
+		Ok(Adapters { joltages, max_gap })
+	}
+
+	/// The number of times each difference in joltage occurs when every adapter is used.
+	fn differences(&self) -> std::collections::BTreeMap<u64, usize> {
+		let mut result: std::collections::BTreeMap<_, _> = Default::default();
+		for pair in self.joltages.windows(2) {
+			*result.entry(pair[1] - pair[0]).or_default() += 1;
+		}
+		result
+	}
+
+	/// The indices of the adapters that an adapter can be directly followed by.
+	fn successors(&self, i: usize) -> impl Iterator<Item = usize> + '_ {
+		let joltage = self.joltages[i];
+		((i + 1)..self.joltages.len()).take_while(move |&j| self.joltages[j] - joltage <= self.max_gap)
+	}
+
+	/// For every adapter, the number of ways to chain adapters from it to the device.
+	fn num_arrangements_to_device(&self) -> Vec<num_bigint::BigUint> {
+		let mut result = vec![num_bigint::BigUint::default(); self.joltages.len()];
+		for i in (0..self.joltages.len()).rev() {
+			result[i] =
+				if i == self.joltages.len() - 1 {
+					1_u8.into()
+				}
+				else {
+					self.successors(i).map(|j| &result[j]).sum()
+				};
+		}
+		result
+	}
+
+	fn num_arrangements(&self) -> num_bigint::BigUint {
+		self.num_arrangements_to_device().swap_remove(0)
+	}
+
+	/// Whether every arrangement uses the adapter at the given index.
+	///
+	/// An arrangement can only skip an adapter if the adapters on either side of it are close enough to connect directly.
+	/// The outlet and the device are always used.
+	fn is_mandatory(&self, i: usize) -> bool {
+		i == 0 || i == self.joltages.len() - 1 || self.joltages[i + 1] - self.joltages[i - 1] > self.max_gap
+	}
+
+	/// The joltages of the adapters that every arrangement uses, not including the outlet and the device.
+	fn mandatory_adapters(&self) -> Vec<u64> {
+		(1..(self.joltages.len() - 1)).filter(|&i| self.is_mandatory(i)).map(|i| self.joltages[i]).collect()
+	}
+
+	/// The joltages of the adapters that some arrangements don't use.
+	fn optional_adapters(&self) -> Vec<u64> {
+		(1..(self.joltages.len() - 1)).filter(|&i| !self.is_mandatory(i)).map(|i| self.joltages[i]).collect()
+	}
+
+	/// Splits the chain at mandatory adapters into segments that can be arranged independently of each other,
+	/// so the number of arrangements of the whole chain is the product of the number of arrangements of each segment.
+	///
+	/// Segments without any optional adapters only have one arrangement, so they are not included.
+	fn segments(&self) -> Vec<Segment> {
+		let mut result = vec![];
 
-	adapters.sort_unstable();
+		let mut start = 0;
+		for end in 1..self.joltages.len() {
+			if !self.is_mandatory(end) {
+				continue;
+			}
+
+			if end - start > 1 {
+				let segment = Adapters { joltages: self.joltages[start..=end].to_owned(), max_gap: self.max_gap };
+				let num_arrangements = segment.num_arrangements();
+				result.push(Segment { joltages: segment.joltages, num_arrangements });
+			}
+
+			start = end;
+		}
+
+		result
+	}
+
+	/// All the arrangements of adapters that connect the outlet to the device, in lexicographic order of the adapters they use.
+	/// The first arrangement uses every adapter.
+	fn arrangements(&self) -> Arrangements<'_> {
+		Arrangements { adapters: self, path: vec![], done: false }
+	}
+
+	/// The arrangement at the given index in the order of [`Adapters::arrangements`], without enumerating the ones before it.
+	///
+	/// Picking a uniformly random index below [`Adapters::num_arrangements`] samples a uniformly random arrangement.
+	fn arrangement(&self, index: &num_bigint::BigUint) -> Option<Vec<u64>> {
+		let num_arrangements_to_device = self.num_arrangements_to_device();
+
+		let mut index = index.clone();
+		if index >= num_arrangements_to_device[0] {
+			return None;
+		}
 
-	let device = adapters.last().ok_or("no adapters found in input")? + 3;
-	adapters.push(device);
+		let mut i = 0;
+		let mut result = vec![self.joltages[i]];
+		while i < self.joltages.len() - 1 {
+			for j in self.successors(i) {
+				if index < num_arrangements_to_device[j] {
+					i = j;
+					break;
+				}
 
-	Ok(adapters)
+				index -= &num_arrangements_to_device[j];
+			}
+
+			result.push(self.joltages[i]);
+		}
+
+		Some(result)
+	}
 }
 
-fn part1(adapters: &[u64]) -> Result<usize, super::Error> {
-	let (num_one_diffs, num_three_diffs) =
-		adapters.iter()
-		.zip(adapters.iter().skip(1))
-		.try_fold((0, 0), |(num_one_diffs, num_three_diffs), (&a, &b)| match b - a {
-			0 | 2 => Ok((num_one_diffs, num_three_diffs)),
-			1 => Ok((num_one_diffs + 1, num_three_diffs)),
-			3 => Ok((num_one_diffs, num_three_diffs + 1)),
-			4..=u64::MAX => Err("no solution"),
-		})?;
-	Ok(num_one_diffs * num_three_diffs)
+/// A part of a chain of adapters that starts and ends with mandatory adapters, and only has optional adapters in between.
+#[derive(Debug, Eq, PartialEq)]
+struct Segment {
+	joltages: Vec<u64>,
+	num_arrangements: num_bigint::BigUint,
 }
 
-fn part2(adapters: &[u64]) -> u64 {
-	let mut num_paths = (0, 0, 1);
-
-	let a = std::iter::repeat(0).take(2).chain(adapters.iter().copied());
-	let b = std::iter::repeat(0).take(1).chain(adapters.iter().copied());
-	let c = adapters.iter().copied();
-	let d = adapters.iter().copied().skip(1);
-
-	for (((a, b), c), d) in a.zip(b).zip(c).zip(d) {
-		num_paths = (
-			num_paths.1,
-			num_paths.2,
-			if d - a <= 3 { num_paths.0 } else { 0 } +
-			if d - b <= 3 { num_paths.1 } else { 0 } +
-			if d - c <= 3 { num_paths.2 } else { 0 },
-		);
+/// An iterator over the arrangements of adapters. See [`Adapters::arrangements`].
+#[derive(Debug)]
+struct Arrangements<'a> {
+	adapters: &'a Adapters,
+
+	/// The indices of the adapters in the previous arrangement.
+	path: Vec<usize>,
+
+	done: bool,
+}
+
+impl Iterator for Arrangements<'_> {
+	type Item = Vec<u64>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.done {
+			return None;
+		}
+
+		let last = self.adapters.joltages.len() - 1;
+
+		if self.path.is_empty() {
+			self.path.push(0);
+		}
+		else {
+			// Find the last adapter that can be swapped for the one after it, swap it, and then use every adapter after that.
+			// Every adapter can reach the device, since the gaps between consecutive adapters are small enough.
+			loop {
+				let i = self.path.pop().expect("path always contains the outlet");
+				match self.path.last() {
+					Some(&previous) if i < last && self.adapters.joltages[i + 1] - self.adapters.joltages[previous] <= self.adapters.max_gap => {
+						self.path.push(i + 1);
+						break;
+					},
+					Some(_) => (),
+					None => {
+						self.done = true;
+						return None;
+					},
+				}
+			}
+		}
+
+		let mut i = *self.path.last().expect("path always contains the outlet");
+		while i < last {
+			i += 1;
+			self.path.push(i);
+		}
+
+		Some(self.path.iter().map(|&i| self.adapters.joltages[i]).collect())
 	}
+}
 
-	num_paths.2
+fn part1(adapters: &Adapters) -> usize {
+	let differences = adapters.differences();
+	let num_one_diffs = differences.get(&1).copied().unwrap_or_default();
+	let num_three_diffs = differences.get(&3).copied().unwrap_or_default();
+	num_one_diffs * num_three_diffs
+}
+
+fn part2(adapters: &Adapters) -> Result<u64, super::Error> {
+	let result = num_traits::ToPrimitive::to_u64(&adapters.num_arrangements()).ok_or("number of arrangements does not fit in u64")?;
+	Ok(result)
 }
 
 #[cfg(test)]
@@ -122,8 +308,8 @@ mod tests {
 			(INPUT1, 35),
 			(INPUT2, 220),
 		] {
-			let adapters = super::parse_adapters(input.split('\n').map(|line| Ok(line.parse()?))).unwrap();
-			assert_eq!(super::part1(&adapters).unwrap(), expected);
+			let adapters = super::Adapters::parse(input.split('\n').map(|line| Ok(line.parse()?)), 3).unwrap();
+			assert_eq!(super::part1(&adapters), expected);
 		}
 	}
 
@@ -133,8 +319,69 @@ mod tests {
 			(INPUT1, 8),
 			(INPUT2, 19208),
 		] {
-			let adapters = super::parse_adapters(input.split('\n').map(|line| Ok(line.parse()?))).unwrap();
-			assert_eq!(super::part2(&adapters), expected);
+			let adapters = super::Adapters::parse(input.split('\n').map(|line| Ok(line.parse()?)), 3).unwrap();
+			assert_eq!(super::part2(&adapters).unwrap(), expected);
 		}
 	}
+
+	#[test]
+	fn differences() {
+		let adapters = super::Adapters::parse(INPUT2.split('\n').map(|line| Ok(line.parse()?)), 3).unwrap();
+		assert_eq!(adapters.differences().into_iter().collect::<Vec<_>>(), [(1, 22), (3, 10)]);
+
+		let adapters = super::Adapters::parse(INPUT1.split('\n').map(|line| Ok(line.parse()?)), 4).unwrap();
+		assert_eq!(adapters.differences().into_iter().collect::<Vec<_>>(), [(1, 7), (3, 4), (4, 1)]);
+
+		assert!(super::Adapters::parse(INPUT1.split('\n').map(|line| Ok(line.parse()?)), 2).is_err());
+	}
+
+	#[test]
+	fn arrangements() {
+		let adapters = super::Adapters::parse(INPUT1.split('\n').map(|line| Ok(line.parse()?)), 3).unwrap();
+
+		let arrangements: Vec<_> = adapters.arrangements().collect();
+		assert_eq!(arrangements, [
+			vec![0, 1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19, 22],
+			vec![0, 1, 4, 5, 6, 7, 10, 12, 15, 16, 19, 22],
+			vec![0, 1, 4, 5, 7, 10, 11, 12, 15, 16, 19, 22],
+			vec![0, 1, 4, 5, 7, 10, 12, 15, 16, 19, 22],
+			vec![0, 1, 4, 6, 7, 10, 11, 12, 15, 16, 19, 22],
+			vec![0, 1, 4, 6, 7, 10, 12, 15, 16, 19, 22],
+			vec![0, 1, 4, 7, 10, 11, 12, 15, 16, 19, 22],
+			vec![0, 1, 4, 7, 10, 12, 15, 16, 19, 22],
+		]);
+
+		for (index, arrangement) in arrangements.iter().enumerate() {
+			assert_eq!(adapters.arrangement(&index.into()).as_ref(), Some(arrangement));
+		}
+		assert_eq!(adapters.arrangement(&arrangements.len().into()), None);
+
+		let adapters = super::Adapters::parse(INPUT2.split('\n').map(|line| Ok(line.parse()?)), 3).unwrap();
+		assert_eq!(adapters.arrangements().count(), 19208);
+
+		// A chain of adapters one jolt apart has tribonacci-many arrangements, which overflows u64 long before the chain gets long.
+		let adapters = super::Adapters::parse((1..=200).map(Ok), 3).unwrap();
+		assert_eq!(adapters.num_arrangements().to_string(), "52622583840983769603765180599790256716084480555530641");
+		assert!(super::part2(&adapters).is_err());
+	}
+
+	#[test]
+	fn segments() {
+		let adapters = super::Adapters::parse(INPUT1.split('\n').map(|line| Ok(line.parse()?)), 3).unwrap();
+		assert_eq!(adapters.mandatory_adapters(), [1, 4, 7, 10, 12, 15, 16, 19]);
+		assert_eq!(adapters.optional_adapters(), [5, 6, 11]);
+		assert_eq!(adapters.segments(), [
+			super::Segment { joltages: vec![4, 5, 6, 7], num_arrangements: 4_u8.into() },
+			super::Segment { joltages: vec![10, 11, 12], num_arrangements: 2_u8.into() },
+		]);
+
+		let adapters = super::Adapters::parse(INPUT2.split('\n').map(|line| Ok(line.parse()?)), 3).unwrap();
+		let num_arrangements: num_bigint::BigUint = adapters.segments().iter().map(|segment| &segment.num_arrangements).product();
+		assert_eq!(num_arrangements, 19208_u16.into());
+
+		// Every adapter is mandatory when they are all as far apart as possible.
+		let adapters = super::Adapters::parse([3, 6, 9].iter().copied().map(Ok), 3).unwrap();
+		assert_eq!(adapters.mandatory_adapters(), [3, 6, 9]);
+		assert!(adapters.segments().is_empty());
+	}
 }
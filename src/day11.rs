@@ -1,8 +1,8 @@
 pub(super) fn run() -> Result<(), super::Error> {
-	let (seats, num_rows, num_cols) = parse_seats(super::read_input_lines::<String>("day11")?)?;
+	let seats = Seats::parse(super::read_input_lines::<String>("day11")?)?;
 
 	{
-		let result = part1(seats.clone(), num_rows, num_cols);
+		let result = part1(&seats)?;
 
 		println!("11a: {result}");
 
@@ -10,117 +10,264 @@ pub(super) fn run() -> Result<(), super::Error> {
 	}
 
 	{
-		let result = part2(seats, num_rows, num_cols);
+		let result = part2(&seats)?;
 
 		println!("11b: {result}");
 
 		assert_eq!(result, 2119);
+
+		// Seats that only stay occupied with no occupied neighbors fill up and empty out forever.
+		let rules = Rules { survival: 0..=0, max_generations: Some(100), ..PART1_RULES };
+		let simulation = simulate(&seats, &rules);
+		assert_eq!(simulation.outcome, Outcome::Cycle { period: 2 });
+		assert!(simulation.num_generations < 100);
 	}
 
 	Ok(())
 }
 
-fn parse_seats(input: impl Iterator<Item = Result<impl AsRef<str>, super::Error>>) ->
-	Result<(std::collections::BTreeMap<(usize, usize), Seat>, usize, usize), super::Error>
-{
-	let mut seats: std::collections::BTreeMap<(usize, usize), Seat> = Default::default();
-
-	for (row, line) in input.enumerate() {
-		let line = line?;
-		let line = line.as_ref();
-
-		for (col, c) in line.chars().enumerate() {
-			match c {
-				'L' => { seats.insert((row, col), Seat::Empty); },
-				'#' => { seats.insert((row, col), Seat::Occupied); },
-				'.' => (),
-				c => return Err(format!("unexpected character {c:?}").into()),
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Seats {
+	num_rows: usize,
+	num_cols: usize,
+
+	/// The seat at each position in row-major order, or `None` if the position is floor.
+	grid: Vec<Option<Seat>>,
+}
+
+impl Seats {
+	fn parse(input: impl Iterator<Item = Result<impl AsRef<str>, super::Error>>) -> Result<Self, super::Error> {
+		let mut rows = vec![];
+
+		for line in input {
+			let line = line?;
+			let line = line.as_ref();
+			if line.is_empty() {
+				continue;
 			}
+
+			let row: Vec<_> =
+				line.chars()
+				.map(|c| match c {
+					'L' => Ok(Some(Seat::Empty)),
+					'#' => Ok(Some(Seat::Occupied)),
+					'.' => Ok(None),
+					c => Err(format!("unexpected character {c:?}")),
+				})
+				.collect::<Result<_, _>>()?;
+			rows.push(row);
 		}
+
+		let num_rows = rows.len();
+		let num_cols = rows.iter().map(Vec::len).max().unwrap_or_default();
+
+		let mut grid = Vec::with_capacity(num_rows * num_cols);
+		for mut row in rows {
+			row.resize(num_cols, None);
+			grid.extend(row);
+		}
+
+		Ok(Seats { num_rows, num_cols, grid })
+	}
+
+	fn num_occupied(&self) -> usize {
+		self.grid.iter().filter(|&&seat| seat == Some(Seat::Occupied)).count()
 	}
 
-	let (num_rows, num_cols) =
-		seats.keys()
-		.fold((0, 0), |(num_rows, num_cols), &(row, col)| (std::cmp::max(num_rows, row + 1), std::cmp::max(num_cols, col + 1)));
+	/// Finds the neighbors of every seat, which are the nearest seats within `check_distance` positions in each of the eight directions.
+	fn neighbors(&self, check_distance: usize) -> Adjacency {
+		const DIRECTIONS: [(isize, isize); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+
+		let mut result = Adjacency { seats: vec![], offsets: vec![0], neighbors: vec![] };
+
+		for row in 0..self.num_rows {
+			for col in 0..self.num_cols {
+				let index = row * self.num_cols + col;
+				if self.grid[index].is_none() {
+					continue;
+				}
+
+				for &(d_row, d_col) in &DIRECTIONS {
+					let (mut neighbor_row, mut neighbor_col) = (row, col);
+					for _ in 0..check_distance {
+						match (step(neighbor_row, d_row, self.num_rows), step(neighbor_col, d_col, self.num_cols)) {
+							(Some(row), Some(col)) => (neighbor_row, neighbor_col) = (row, col),
+							_ => break,
+						}
+
+						let neighbor_index = neighbor_row * self.num_cols + neighbor_col;
+						if self.grid[neighbor_index].is_some() {
+							result.neighbors.push(neighbor_index);
+							break;
+						}
+					}
+				}
+
+				result.seats.push(index);
+				result.offsets.push(result.neighbors.len());
+			}
+		}
+
+		result
+	}
+}
+
+fn step(i: usize, delta: isize, len: usize) -> Option<usize> {
+	let i = if delta < 0 { i.checked_sub(delta.unsigned_abs())? } else { i.checked_add(delta.unsigned_abs())? };
+	(i < len).then(|| i)
+}
+
+/// The neighbors of every seat as a flat adjacency array.
+///
+/// The neighbors of `seats[i]` are `neighbors[offsets[i]..offsets[i + 1]]`. Both are indices into [`Seats::grid`].
+#[derive(Debug)]
+struct Adjacency {
+	seats: Vec<usize>,
+	offsets: Vec<usize>,
+	neighbors: Vec<usize>,
+}
 
-	Ok((seats, num_rows, num_cols))
+fn part1(seats: &Seats) -> Result<usize, super::Error> {
+	solve(seats, &PART1_RULES)
 }
 
-fn part1(
-	seats: std::collections::BTreeMap<(usize, usize), Seat>,
-	num_rows: usize, num_cols: usize,
-) -> usize {
-	solve(seats, num_rows, num_cols, 1, 4)
+fn part2(seats: &Seats) -> Result<usize, super::Error> {
+	solve(seats, &PART2_RULES)
 }
 
-fn part2(
-	seats: std::collections::BTreeMap<(usize, usize), Seat>,
-	num_rows: usize, num_cols: usize,
-) -> usize {
-	solve(seats, num_rows, num_cols, usize::max_value(), 5)
+fn solve(seats: &Seats, rules: &Rules) -> Result<usize, super::Error> {
+	let simulation = simulate(seats, rules);
+	match simulation.outcome {
+		Outcome::Stable => Ok(simulation.seats.num_occupied()),
+		outcome => Err(format!("seats did not stabilize: {outcome:?}").into()),
+	}
 }
 
-fn solve(
-	mut seats: std::collections::BTreeMap<(usize, usize), Seat>,
-	num_rows: usize, num_cols: usize,
+const PART1_RULES: Rules = Rules {
+	check_distance: 1,
+	birth: 0..=0,
+	survival: 0..=3,
+	max_generations: None,
+};
+
+const PART2_RULES: Rules = Rules {
+	check_distance: usize::max_value(),
+	birth: 0..=0,
+	survival: 0..=4,
+	max_generations: None,
+};
+
+#[derive(Clone, Debug)]
+struct Rules {
+	/// How many positions to look in each of the eight directions for the nearest seat. 1 means only the adjacent positions.
 	check_distance: usize,
-	min_num_occupied_neighbors_to_become_empty: usize,
-) -> usize {
-	let mut make_occupied = vec![];
-	let mut make_empty = vec![];
-
-	loop {
-		for (&(row, col), &seat) in &seats {
-			let up = (0..row).rev().take(check_distance);
-			let down = ((row + 1)..num_rows).take(check_distance);
-			let left = (0..col).rev().take(check_distance);
-			let right = ((col + 1)..num_cols).take(check_distance);
-
-			let position_iterators: &mut [&mut dyn Iterator<Item = (usize, usize)>] = &mut [
-				&mut up.clone().map(|row| (row, col)),
-				&mut down.clone().map(|row| (row, col)),
-				&mut left.clone().map(|col| (row, col)),
-				&mut right.clone().map(|col| (row, col)),
-				&mut up.clone().zip(left.clone()),
-				&mut up.zip(right.clone()),
-				&mut down.clone().zip(left),
-				&mut down.zip(right),
-			];
-
-			let num_occupied_neighbors: usize =
-				position_iterators.iter_mut()
-				.filter_map(|positions| positions.find_map(|position| seats.get(&position)))
-				.filter(|&&seat| seat == Seat::Occupied)
-				.count();
 
-			match (seat, num_occupied_neighbors) {
-				(Seat::Empty, 0) =>
-					make_occupied.push((row, col)),
+	/// An empty seat becomes occupied if its number of occupied neighbors is in this range.
+	birth: std::ops::RangeInclusive<usize>,
+
+	/// An occupied seat stays occupied if its number of occupied neighbors is in this range.
+	survival: std::ops::RangeInclusive<usize>,
+
+	/// Stop after this many generations even if the seats have not stabilized.
+	max_generations: Option<usize>,
+}
+
+/// Why [`simulate`] stopped.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Outcome {
+	/// The last generation did not change any seats.
+	Stable,
 
-				(Seat::Occupied, num_occupied_neighbors) if num_occupied_neighbors >= min_num_occupied_neighbors_to_become_empty =>
-					make_empty.push((row, col)),
+	/// The last generation repeated the seats from `period` generations before, so the seats would keep changing forever.
+	Cycle { period: usize },
 
-				_ => (),
+	/// The simulation ran for [`Rules::max_generations`] generations.
+	LimitReached,
+}
+
+#[derive(Debug)]
+struct Simulation {
+	seats: Seats,
+
+	/// The number of generations that changed any seats.
+	num_generations: usize,
+
+	outcome: Outcome,
+}
+
+/// Runs the rules until the seats stabilize, repeat, or the generation limit is reached.
+///
+/// The neighbors of each seat are found once up front, and each generation is computed from the previous one into a second buffer,
+/// and then the two buffers are swapped.
+fn simulate(seats: &Seats, rules: &Rules) -> Simulation {
+	// Much faster than looking up neighbors in a `BTreeMap` of seats every generation; see `tests::bench_simulate`
+	let neighbors = seats.neighbors(rules.check_distance);
+
+	let mut current = seats.grid.clone();
+	let mut next = seats.grid.clone();
+
+	// Each generation is identified by which seats are occupied, as a bitset in the order of `neighbors.seats`.
+	let occupied = |grid: &[Option<Seat>]| {
+		let mut result = vec![0_u64; (neighbors.seats.len() + 63) / 64];
+		for (i, &index) in neighbors.seats.iter().enumerate() {
+			if grid[index] == Some(Seat::Occupied) {
+				result[i / 64] |= 1 << (i % 64);
 			}
 		}
+		result
+	};
+	let mut generations: std::collections::HashMap<Vec<u64>, usize> = Default::default();
+	generations.insert(occupied(&current), 0);
+
+	let mut num_generations = 0;
+
+	let outcome = loop {
+		if rules.max_generations.map_or(false, |max_generations| num_generations >= max_generations) {
+			break Outcome::LimitReached;
+		}
 
-		if make_occupied.is_empty() && make_empty.is_empty() {
-			break;
+		let mut changed = false;
+
+		for (i, &index) in neighbors.seats.iter().enumerate() {
+			let seat = current[index].expect("neighbors are only computed for seats");
+
+			let num_occupied_neighbors =
+				neighbors.neighbors[neighbors.offsets[i]..neighbors.offsets[i + 1]].iter()
+				.filter(|&&neighbor_index| current[neighbor_index] == Some(Seat::Occupied))
+				.count();
+
+			let new_seat = match seat {
+				Seat::Empty if rules.birth.contains(&num_occupied_neighbors) => Seat::Occupied,
+				Seat::Occupied if !rules.survival.contains(&num_occupied_neighbors) => Seat::Empty,
+				seat => seat,
+			};
+
+			next[index] = Some(new_seat);
+			changed |= new_seat != seat;
 		}
 
-		for (row, col) in make_occupied.drain(..) {
-			seats.insert((row, col), Seat::Occupied);
+		if !changed {
+			break Outcome::Stable;
 		}
 
-		for (row, col) in make_empty.drain(..) {
-			seats.insert((row, col), Seat::Empty);
+		std::mem::swap(&mut current, &mut next);
+
+		num_generations += 1;
+
+		match generations.entry(occupied(&current)) {
+			std::collections::hash_map::Entry::Occupied(entry) => break Outcome::Cycle { period: num_generations - entry.get() },
+			std::collections::hash_map::Entry::Vacant(entry) => { entry.insert(num_generations); },
 		}
-	}
+	};
 
-	seats.values().filter(|&&seat| seat == Seat::Occupied).count()
+	Simulation {
+		seats: Seats { num_rows: seats.num_rows, num_cols: seats.num_cols, grid: current },
+		num_generations,
+		outcome,
+	}
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 enum Seat {
 	Empty,
 	Occupied,
@@ -143,13 +290,148 @@ L.LLLLL.LL
 
 	#[test]
 	fn part1() {
-		let (seats, num_rows, num_cols) = super::parse_seats(INPUT.split('\n').map(Ok)).unwrap();
-		assert_eq!(super::part1(seats, num_rows, num_cols), 37);
+		let seats = super::Seats::parse(INPUT.split('\n').map(Ok)).unwrap();
+		assert_eq!(super::part1(&seats).unwrap(), 37);
 	}
 
 	#[test]
 	fn part2() {
-		let (seats, num_rows, num_cols) = super::parse_seats(INPUT.split('\n').map(Ok)).unwrap();
-		assert_eq!(super::part2(seats, num_rows, num_cols), 26);
+		let seats = super::Seats::parse(INPUT.split('\n').map(Ok)).unwrap();
+		assert_eq!(super::part2(&seats).unwrap(), 26);
+	}
+
+	#[test]
+	fn simulate() {
+		let seats = super::Seats::parse(INPUT.split('\n').map(Ok)).unwrap();
+
+		let simulation = super::simulate(&seats, &super::PART1_RULES);
+		assert_eq!(simulation.outcome, super::Outcome::Stable);
+		assert_eq!(simulation.num_generations, 5);
+		assert_eq!(simulation.seats.num_occupied(), 37);
+
+		let simulation = super::simulate(&seats, &super::PART2_RULES);
+		assert_eq!(simulation.outcome, super::Outcome::Stable);
+		assert_eq!(simulation.num_generations, 6);
+
+		let rules = super::Rules { max_generations: Some(2), ..super::PART1_RULES };
+		let simulation = super::simulate(&seats, &rules);
+		assert_eq!(simulation.outcome, super::Outcome::LimitReached);
+		assert_eq!(simulation.num_generations, 2);
+		assert_eq!(simulation.seats.num_occupied(), 20);
+
+		// Every seat becomes occupied, and then every seat with a neighbor becomes empty, and so on.
+		let rules = super::Rules { survival: 0..=0, ..super::PART1_RULES };
+		let simulation = super::simulate(&seats, &rules);
+		assert_eq!(simulation.outcome, super::Outcome::Cycle { period: 2 });
+		assert!(super::solve(&seats, &rules).is_err());
+	}
+
+	#[test]
+	fn neighbors() {
+		let seats = super::Seats::parse(["L.L", "...", "#.L"].iter().map(Ok)).unwrap();
+
+		let neighbors = seats.neighbors(1);
+		assert_eq!(neighbors.seats, [0, 2, 6, 8]);
+		assert!(neighbors.neighbors.is_empty());
+
+		let neighbors = seats.neighbors(super::PART2_RULES.check_distance);
+		assert_eq!(neighbors.offsets, [0, 3, 6, 9, 12]);
+		assert_eq!(neighbors.neighbors, [2, 6, 8, 0, 6, 8, 0, 2, 8, 0, 2, 6]);
+	}
+
+	#[test]
+	#[ignore = "benchmark"]
+	fn bench_simulate() {
+		// `simulate` before it was changed to use a dense grid and precomputed neighbors.
+		fn btreemap_simulate(mut seats: std::collections::BTreeMap<(usize, usize), super::Seat>, num_rows: usize, num_cols: usize, rules: &super::Rules) -> usize {
+			let mut make_occupied = vec![];
+			let mut make_empty = vec![];
+
+			loop {
+				for (&(row, col), &seat) in &seats {
+					let check_distance = rules.check_distance;
+
+					let up = (0..row).rev().take(check_distance);
+					let down = ((row + 1)..num_rows).take(check_distance);
+					let left = (0..col).rev().take(check_distance);
+					let right = ((col + 1)..num_cols).take(check_distance);
+
+					let position_iterators: &mut [&mut dyn Iterator<Item = (usize, usize)>] = &mut [
+						&mut up.clone().map(|row| (row, col)),
+						&mut down.clone().map(|row| (row, col)),
+						&mut left.clone().map(|col| (row, col)),
+						&mut right.clone().map(|col| (row, col)),
+						&mut up.clone().zip(left.clone()),
+						&mut up.zip(right.clone()),
+						&mut down.clone().zip(left),
+						&mut down.zip(right),
+					];
+
+					let num_occupied_neighbors: usize =
+						position_iterators.iter_mut()
+						.filter_map(|positions| positions.find_map(|position| seats.get(&position)))
+						.filter(|&&seat| seat == super::Seat::Occupied)
+						.count();
+
+					match seat {
+						super::Seat::Empty if rules.birth.contains(&num_occupied_neighbors) =>
+							make_occupied.push((row, col)),
+
+						super::Seat::Occupied if !rules.survival.contains(&num_occupied_neighbors) =>
+							make_empty.push((row, col)),
+
+						_ => (),
+					}
+				}
+
+				if make_occupied.is_empty() && make_empty.is_empty() {
+					break;
+				}
+
+				for (row, col) in make_occupied.drain(..) {
+					seats.insert((row, col), super::Seat::Occupied);
+				}
+
+				for (row, col) in make_empty.drain(..) {
+					seats.insert((row, col), super::Seat::Empty);
+				}
+			}
+
+			seats.values().filter(|&&seat| seat == super::Seat::Occupied).count()
+		}
+
+		const NUM_ROWS: usize = 200;
+		const NUM_COLS: usize = 200;
+
+		let mut random = super::super::random_numbers();
+		let input: Vec<String> =
+			(0..NUM_ROWS)
+			.map(|_| (0..NUM_COLS).map(|_| if random() % 4 == 0 { '.' } else { 'L' }).collect())
+			.collect();
+
+		let seats = super::Seats::parse(input.iter().map(Ok)).unwrap();
+
+		let btreemap_seats: std::collections::BTreeMap<_, _> =
+			seats.grid.iter().enumerate()
+			.filter_map(|(index, &seat)| seat.map(|seat| ((index / NUM_COLS, index % NUM_COLS), seat)))
+			.collect();
+
+		let start = std::time::Instant::now();
+		let expected: Vec<_> =
+			[&super::PART1_RULES, &super::PART2_RULES].iter()
+			.map(|rules| btreemap_simulate(btreemap_seats.clone(), NUM_ROWS, NUM_COLS, rules))
+			.collect();
+		let btreemap_elapsed = start.elapsed();
+
+		let start = std::time::Instant::now();
+		let actual: Vec<_> =
+			[&super::PART1_RULES, &super::PART2_RULES].iter()
+			.map(|rules| super::solve(&seats, rules).unwrap())
+			.collect();
+		let dense_elapsed = start.elapsed();
+
+		assert_eq!(actual, expected);
+
+		println!("BTreeMap: {btreemap_elapsed:?}, dense grid: {dense_elapsed:?}");
 	}
 }
@@ -2,7 +2,7 @@ pub(super) fn run() -> Result<(), super::Error> {
 	let actions = Action::parse(super::read_input_lines::<Action>("day12")?)?;
 
 	{
-		let result = part1(&actions);
+		let result = part1(&actions)?;
 
 		println!("12a: {result}");
 
@@ -10,31 +10,135 @@ pub(super) fn run() -> Result<(), super::Error> {
 	}
 
 	{
-		let result = part2(&actions);
+		let result = part2(&actions)?;
 
 		println!("12b: {result}");
 
 		assert_eq!(result, 58637);
+
+		// Rotating by half the angle twice is the same as rotating by the whole angle once, give or take rounding errors.
+		let actions: Vec<_> =
+			actions.iter()
+			.flat_map(|&action| match action {
+				Action::Rotate(degrees) => vec![Action::Rotate(degrees / 2); 2],
+				action => vec![action],
+			})
+			.collect();
+		let mut ship = Ship::<f64>::new("waypoint".parse()?);
+		for &action in &actions {
+			ship.update(action)?;
+		}
+		assert_eq!(manhattan_distance(ship.pos), result);
+
+		// The ship ends up where it is, so it can't have been less far than that at some point.
+		assert!(ship.max_distance() >= result);
+		let (min, max) = ship.bounding_box();
+		assert!(min.re <= ship.pos.re && ship.pos.re <= max.re && min.im <= ship.pos.im && ship.pos.im <= max.im);
+		assert!(ship.total_distance() >= ship.pos.norm());
+		assert_eq!(ship.to_csv().lines().count(), actions.len() + 2);
+		assert!(ship.to_svg().starts_with("<svg "));
 	}
 
 	Ok(())
 }
 
-type Vector = num_complex::Complex<i64>;
+type Vector<T> = num_complex::Complex<T>;
+
+const EAST: Vector<i64> = Vector::new(1, 0);
+const NORTH: Vector<i64> = Vector::new(0, 1);
+const SOUTH: Vector<i64> = Vector::new(0, -1);
+const WEST: Vector<i64> = Vector::new(-1, 0);
+
+/// The type of the coordinates of positions and directions.
+trait Coordinate: Copy + PartialOrd + std::fmt::Display + num_traits::NumAssign + num_traits::Signed {
+	fn from_i64(value: i64) -> Self;
+
+	/// The unit vector that rotates by the given number of degrees counter-clockwise, or `None` if it can't be represented.
+	fn rotation(degrees: i64) -> Option<Vector<Self>>;
+
+	fn to_f64(self) -> f64;
+
+	/// Rounded to the nearest integer.
+	fn to_i64(self) -> i64;
+}
+
+/// Exact, but can only turn by multiples of 90 degrees.
+impl Coordinate for i64 {
+	fn from_i64(value: i64) -> Self {
+		value
+	}
+
+	fn rotation(degrees: i64) -> Option<Vector<Self>> {
+		quarter_turns(degrees)
+	}
+
+	fn to_f64(self) -> f64 {
+		#[allow(clippy::cast_precision_loss)]
+		let result = self as f64;
+		result
+	}
+
+	fn to_i64(self) -> i64 {
+		self
+	}
+}
+
+/// Can turn by any angle. Turns by multiples of 90 degrees are still exact.
+impl Coordinate for f64 {
+	fn from_i64(value: i64) -> Self {
+		value.to_f64()
+	}
+
+	fn rotation(degrees: i64) -> Option<Vector<Self>> {
+		Some(match quarter_turns(degrees) {
+			Some(rotation) => convert(rotation),
+			None => Vector::from_polar(1., Self::from_i64(degrees % 360).to_radians()),
+		})
+	}
+
+	fn to_f64(self) -> f64 {
+		self
+	}
+
+	fn to_i64(self) -> i64 {
+		#[allow(clippy::cast_possible_truncation)]
+		let result = self.round() as i64;
+		result
+	}
+}
+
+/// The unit vector that rotates by the given number of degrees counter-clockwise, if it is a multiple of 90.
+fn quarter_turns(degrees: i64) -> Option<Vector<i64>> {
+	if degrees % 90 != 0 {
+		return None;
+	}
 
-const EAST: Vector = Vector::new(1, 0);
-const NORTH: Vector = Vector::new(0, 1);
-const SOUTH: Vector = Vector::new(0, -1);
-const WEST: Vector = Vector::new(-1, 0);
+	Some(match degrees.rem_euclid(360) / 90 {
+		0 => EAST,
+		1 => NORTH,
+		2 => WEST,
+		3 => SOUTH,
+		_ => unreachable!(),
+	})
+}
 
-const ROTATE_LEFT: Vector = Vector::new(0, 1);
-const ROTATE_RIGHT: Vector = Vector::new(0, -1);
+fn convert<T: Coordinate>(vector: Vector<i64>) -> Vector<T> {
+	Vector::new(T::from_i64(vector.re), T::from_i64(vector.im))
+}
+
+/// The Manhattan distance of the given position from the origin, rounded to the nearest integer.
+fn manhattan_distance<T: Coordinate>(pos: Vector<T>) -> i64 {
+	pos.l1_norm().to_i64()
+}
 
 #[derive(Clone, Copy, Debug)]
 enum Action {
 	Forward(i64),
-	Rotate(Vector),
-	Translate(Vector),
+
+	/// Rotate by this many degrees counter-clockwise.
+	Rotate(i64),
+
+	Translate(Vector<i64>),
 }
 
 impl Action {
@@ -49,64 +153,186 @@ impl std::str::FromStr for Action {
 	type Err = super::Error;
 
 	fn from_str(s: &str) -> Result<Self, Self::Err> {
-		let amount: i64 = s[1..].parse().map_err(|err| format!("invalid action {s:?}: {err}"))?;
-
-		Ok(match &s[..1] {
-			"E" => Action::Translate(EAST * amount),
-			"F" => Action::Forward(amount),
-			"L" => Action::Rotate(num_traits::Pow::pow(&ROTATE_LEFT, amount / 90)),
-			"N" => Action::Translate(NORTH * amount),
-			"R" => Action::Rotate(num_traits::Pow::pow(&ROTATE_RIGHT, amount / 90)),
-			"S" => Action::Translate(SOUTH * amount),
-			"W" => Action::Translate(WEST * amount),
+		let mut chars = s.chars();
+		let kind = chars.next().ok_or_else(|| format!("invalid action {s:?}: empty"))?;
+		let amount: i64 = chars.as_str().parse().map_err(|err| format!("invalid action {s:?}: {err}"))?;
+
+		let distance = || -> Result<i64, super::Error> {
+			if amount < 0 {
+				return Err(format!("invalid action {s:?}: distance must not be negative").into());
+			}
+			Ok(amount)
+		};
+
+		Ok(match kind {
+			'E' => Action::Translate(EAST * distance()?),
+			'F' => Action::Forward(distance()?),
+			'L' => Action::Rotate(amount),
+			'N' => Action::Translate(NORTH * distance()?),
+			'R' => Action::Rotate(amount.checked_neg().ok_or_else(|| format!("invalid action {s:?}: angle out of range"))?),
+			'S' => Action::Translate(SOUTH * distance()?),
+			'W' => Action::Translate(WEST * distance()?),
 			_ => return Err(format!("invalid action {s:?}").into()),
 		})
 	}
 }
 
-#[derive(Clone, Copy, Debug)]
-struct Ship {
-	pos: Vector,
-	waypoint: Vector,
-	translate_waypoint: bool,
-}
-
-impl Ship {
-	fn update(&mut self, action: Action) {
-		match action {
-			Action::Forward(amount) => self.pos += self.waypoint * amount,
-			Action::Rotate(vector) => self.waypoint *= vector,
-			Action::Translate(vector) => *(if self.translate_waypoint { &mut self.waypoint } else { &mut self.pos }) += vector,
+/// How the ship interprets actions.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Mode {
+	/// `N` / `S` / `E` / `W` move the ship, `L` / `R` turn the ship, and `F` moves the ship in the direction it faces.
+	Ship,
+
+	/// `N` / `S` / `E` / `W` move the waypoint, `L` / `R` rotate the waypoint around the ship, and `F` moves the ship to the waypoint.
+	Waypoint,
+
+	/// Like [`Mode::Ship`], except that `N` / `S` / `E` / `W` move the ship forward / backward / right / left of the direction it faces.
+	Relative,
+}
+
+impl std::str::FromStr for Mode {
+	type Err = super::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Ok(match s {
+			"ship" => Mode::Ship,
+			"waypoint" => Mode::Waypoint,
+			"relative" => Mode::Relative,
+			_ => return Err(format!("invalid mode {s:?}").into()),
+		})
+	}
+}
+
+#[derive(Clone, Debug)]
+struct Ship<T> {
+	pos: Vector<T>,
+
+	/// The direction the ship faces, or the position of the waypoint relative to the ship in [`Mode::Waypoint`].
+	waypoint: Vector<T>,
+
+	mode: Mode,
+
+	/// The position and waypoint of the ship initially and after every action.
+	history: Vec<Step<T>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Step<T> {
+	pos: Vector<T>,
+	waypoint: Vector<T>,
+}
+
+impl<T: Coordinate> Ship<T> {
+	fn new(mode: Mode) -> Self {
+		let pos = Vector::new(T::zero(), T::zero());
+		let waypoint = convert(match mode {
+			Mode::Ship | Mode::Relative => EAST,
+			Mode::Waypoint => Vector::new(10, 1),
+		});
+
+		Ship {
+			pos,
+			waypoint,
+			mode,
+			history: vec![Step { pos, waypoint }],
+		}
+	}
+
+	fn update(&mut self, action: Action) -> Result<(), super::Error> {
+		match (action, self.mode) {
+			(Action::Forward(amount), _) => self.pos += self.waypoint * T::from_i64(amount),
+			(Action::Rotate(degrees), _) => self.waypoint *= T::rotation(degrees).ok_or_else(|| format!("cannot turn by {degrees} degrees exactly"))?,
+			(Action::Translate(vector), Mode::Ship) => self.pos += convert(vector),
+			(Action::Translate(vector), Mode::Waypoint) => self.waypoint += convert(vector),
+			// Rotate the translation so that north becomes the direction the ship faces.
+			(Action::Translate(vector), Mode::Relative) => self.pos += convert::<T>(vector) / convert(NORTH) * self.waypoint,
+		}
+
+		self.history.push(Step { pos: self.pos, waypoint: self.waypoint });
+
+		Ok(())
+	}
+
+	/// The largest Manhattan distance from the origin that the ship reached.
+	fn max_distance(&self) -> i64 {
+		self.history.iter().map(|step| manhattan_distance(step.pos)).max().unwrap_or_default()
+	}
+
+	/// The smallest and largest coordinates that the ship reached.
+	fn bounding_box(&self) -> (Vector<T>, Vector<T>) {
+		let min = |a: T, b: T| if b < a { b } else { a };
+		let max = |a: T, b: T| if b > a { b } else { a };
+
+		self.history.iter().fold((self.pos, self.pos), |(lo, hi), step| (
+			Vector::new(min(lo.re, step.pos.re), min(lo.im, step.pos.im)),
+			Vector::new(max(hi.re, step.pos.re), max(hi.im, step.pos.im)),
+		))
+	}
+
+	/// The total length of the straight lines that the ship sailed along.
+	fn total_distance(&self) -> f64 {
+		self.history.windows(2).map(|steps| {
+			let line = steps[1].pos - steps[0].pos;
+			line.re.to_f64().hypot(line.im.to_f64())
+		}).sum()
+	}
+
+	/// The history as CSV, with a header row. Waypoints are relative to the ship, like [`Ship::waypoint`].
+	fn to_csv(&self) -> String {
+		use std::fmt::Write;
+
+		let mut result = "step,x,y,waypoint_x,waypoint_y\n".to_owned();
+		for (i, step) in self.history.iter().enumerate() {
+			// Add 0 so that -0 becomes 0.
+			writeln!(
+				result,
+				"{i},{},{},{},{}",
+				step.pos.re + T::zero(), step.pos.im + T::zero(), step.waypoint.re + T::zero(), step.waypoint.im + T::zero(),
+			).expect("cannot fail to write to String");
 		}
+		result
+	}
+
+	/// The path of the ship as an SVG image, with north at the top.
+	fn to_svg(&self) -> String {
+		use std::fmt::Write;
+
+		let (min, max) = self.bounding_box();
+
+		let mut result = String::new();
+		writeln!(
+			result,
+			r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+			min.re.to_f64() - 1., -max.im.to_f64() - 1., (max.re - min.re).to_f64() + 2., (max.im - min.im).to_f64() + 2.,
+		).expect("cannot fail to write to String");
+
+		// SVG's y axis points down. Subtract from 0 instead of negating, so that 0 doesn't become -0.
+		let points: Vec<_> = self.history.iter().map(|step| format!("{},{}", step.pos.re, T::zero() - step.pos.im)).collect();
+		writeln!(result, r#"<polyline fill="none" stroke="black" points="{}" />"#, points.join(" ")).expect("cannot fail to write to String");
+
+		result.push_str("</svg>\n");
+		result
 	}
 }
 
-fn part1(actions: &[Action]) -> i64 {
-	let mut ship = Ship {
-		pos: Default::default(),
-		waypoint: EAST,
-		translate_waypoint: false,
-	};
+fn part1(actions: &[Action]) -> Result<i64, super::Error> {
+	let mut ship = Ship::<i64>::new(Mode::Ship);
 
 	for &action in actions {
-		ship.update(action);
+		ship.update(action)?;
 	}
 
-	ship.pos.l1_norm()
+	Ok(manhattan_distance(ship.pos))
 }
 
-fn part2(actions: &[Action]) -> i64 {
-	let mut ship = Ship {
-		pos: Default::default(),
-		waypoint: Vector::new(10, 1),
-		translate_waypoint: true,
-	};
+fn part2(actions: &[Action]) -> Result<i64, super::Error> {
+	let mut ship = Ship::<i64>::new(Mode::Waypoint);
 
 	for &action in actions {
-		ship.update(action);
+		ship.update(action)?;
 	}
 
-	ship.pos.l1_norm()
+	Ok(manhattan_distance(ship.pos))
 }
 
 #[cfg(test)]
@@ -122,12 +348,102 @@ F11\
 	#[test]
 	fn part1() {
 		let actions = super::Action::parse(INPUT.split('\n').map(|line| Ok(line.parse()?))).unwrap();
-		assert_eq!(super::part1(&actions), 25);
+		assert_eq!(super::part1(&actions).unwrap(), 25);
 	}
 
 	#[test]
 	fn part2() {
 		let actions = super::Action::parse(INPUT.split('\n').map(|line| Ok(line.parse()?))).unwrap();
-		assert_eq!(super::part2(&actions), 286);
+		assert_eq!(super::part2(&actions).unwrap(), 286);
+	}
+
+	#[test]
+	fn parse() {
+		assert!("R45".parse::<super::Action>().is_ok());
+		assert!("F-10".parse::<super::Action>().is_err());
+		assert!("X10".parse::<super::Action>().is_err());
+		assert!("".parse::<super::Action>().is_err());
+		assert!("F".parse::<super::Action>().is_err());
+		assert!("F2147483648".parse::<super::Action>().is_ok());
+		assert!("R-9223372036854775808".parse::<super::Action>().is_err());
+
+		assert_eq!(super::quarter_turns(-90), Some(super::SOUTH));
+		assert_eq!(super::quarter_turns(450), Some(super::NORTH));
+		assert_eq!(super::quarter_turns(45), None);
+		assert_eq!(<f64 as super::Coordinate>::rotation(-90), Some(super::Vector::new(0., -1.)));
+		assert!(<f64 as super::Coordinate>::rotation(45).is_some());
+	}
+
+	#[test]
+	fn modes() {
+		let actions = super::Action::parse(INPUT.split('\n').map(str::parse)).unwrap();
+
+		// F10, N3 and F7 all move forward while facing east, and F11 moves south after turning right.
+		let mut ship = super::Ship::<i64>::new("relative".parse().unwrap());
+		for &action in &actions {
+			ship.update(action).unwrap();
+		}
+		assert_eq!(ship.pos, super::Vector::new(20, -11));
+
+		assert!("sideways".parse::<super::Mode>().is_err());
+
+		// Going around a hexagon ends up where it started.
+		let actions = super::Action::parse(["F10", "L60"].iter().cycle().take(12).map(|line| line.parse())).unwrap();
+		let mut ship = super::Ship::<f64>::new(super::Mode::Ship);
+		for &action in &actions {
+			ship.update(action).unwrap();
+		}
+		assert_eq!(super::manhattan_distance(ship.pos), 0);
+		assert!((ship.waypoint - super::Vector::new(1., 0.)).norm() < 1e-9);
+
+		// Only multiples of 90 degrees can be turned by exactly.
+		let mut ship = super::Ship::<i64>::new(super::Mode::Ship);
+		assert!(ship.update(super::Action::Rotate(60)).is_err());
+	}
+
+	#[test]
+	fn history() {
+		let actions = super::Action::parse(INPUT.split('\n').map(str::parse)).unwrap();
+
+		let mut ship = super::Ship::<i64>::new(super::Mode::Waypoint);
+		for &action in &actions {
+			ship.update(action).unwrap();
+		}
+
+		assert_eq!(ship.history.len(), actions.len() + 1);
+		assert_eq!(ship.max_distance(), 286);
+		assert_eq!(ship.bounding_box(), (super::Vector::new(0, -72), super::Vector::new(214, 38)));
+		assert!((ship.total_distance() - (101_f64.sqrt() * 10. + 116_f64.sqrt() * 18.)).abs() < 1e-9);
+
+		assert_eq!(ship.to_csv(), "\
+step,x,y,waypoint_x,waypoint_y
+0,0,0,10,1
+1,100,10,10,1
+2,100,10,10,4
+3,170,38,10,4
+4,170,38,4,-10
+5,214,-72,4,-10
+");
+
+		assert_eq!(ship.to_svg(), r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="-1 -39 216 112">
+<polyline fill="none" stroke="black" points="0,0 100,-10 100,-10 170,-38 170,-38 214,72" />
+</svg>
+"#);
+
+		// Rotating in floating point makes some of the components of the waypoint -0.
+		let actions = super::Action::parse(["L90", "L90", "L90", "F1"].iter().map(|line| line.parse())).unwrap();
+		let mut ship = super::Ship::<f64>::new(super::Mode::Ship);
+		for &action in &actions {
+			ship.update(action).unwrap();
+		}
+
+		assert_eq!(ship.to_csv(), "\
+step,x,y,waypoint_x,waypoint_y
+0,0,0,1,0
+1,0,0,0,1
+2,0,0,-1,0
+3,0,0,0,-1
+4,0,-1,0,-1
+");
 	}
 }
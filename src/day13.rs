@@ -9,20 +9,25 @@ pub(super) fn run() -> Result<(), super::Error> {
 		assert_eq!(result, 153);
 	}
 
-	#[cfg(not(test))]
 	{
-		let result = part2(&bus_ids);
+		let result = part2(&bus_ids)?;
 
 		println!("13b: {result}");
 
-		assert_eq!(
-			result,
-			"https://www.wolframalpha.com/input/?i=ChineseRemainder%28%7b0%2c+38%2c+984%2c+2%2c+6%2c+16%2c+575%2c+24%2c+7%7d%2c+%7b13%2c+41%2c+997%2c+23%2c+19%2c+29%2c+619%2c+37%2c+17%7d%29",
-		);
-
-		let result = 471793476184394;
+		assert_eq!(result, 471793476184394);
 
 		assert!(check_part2(result, &bus_ids));
+
+		// The bus IDs are all prime, so the pattern of departures repeats after their product,
+		// and that is also the period after which the staggered departures of part 2 happen again.
+		let schedule = Schedule::new(&bus_ids);
+		let period = schedule.period()?;
+		assert_eq!(period, schedule.buses.iter().map(|&(bus_id, _)| bus_id as u128).product());
+		assert_eq!(schedule.earliest_departure_with_offsets(&schedule.buses)?, (result, period));
+
+		let departures = schedule.departures_between(earliest_departure_timestamp..(earliest_departure_timestamp + 10));
+		let next_departures = schedule.next_departures(earliest_departure_timestamp, 1)?;
+		assert_eq!(departures.first().map(|&(time, _)| time), next_departures.iter().map(|(_, departures)| departures[0]).min());
 	}
 
 	Ok(())
@@ -45,6 +50,9 @@ fn parse_input(mut input: impl Iterator<Item = Result<impl AsRef<str>, super::Er
 					Ok(None)
 				} else {
 					let id = id.parse().map_err(|err| format!("invalid bus ID {id:?}: {err}"))?;
+					if id == 0 {
+						return Err("invalid bus ID \"0\"".into());
+					}
 					Ok(Some(id))
 				})
 			.collect();
@@ -55,79 +63,184 @@ fn parse_input(mut input: impl Iterator<Item = Result<impl AsRef<str>, super::Er
 }
 
 fn part1(earliest_departure_timestamp: usize, bus_ids: &[Option<usize>]) -> Result<usize, super::Error> {
+	let schedule = Schedule::new(bus_ids);
+
 	let (next_bus_id, next_bus_departure) =
-		bus_ids.iter()
-		.filter_map(|&bus_id| {
-			let bus_id = bus_id?;
-			Some((bus_id, (earliest_departure_timestamp + bus_id - 1) / bus_id * bus_id))
-		})
+		schedule.next_departures(earliest_departure_timestamp, 1)?.into_iter()
+		.filter_map(|(bus_id, departures)| Some((bus_id, *departures.first()?)))
 		.min_by_key(|&(_, next_bus_departure)| next_bus_departure)
 		.ok_or("no solution")?;
 
 	Ok(next_bus_id * (next_bus_departure - earliest_departure_timestamp))
 }
 
-#[cfg(test)]
-fn part2(bus_ids: &[Option<usize>]) -> Result<usize, super::Error> {
-	let max_bus_id_parameters =
-		bus_ids.iter()
-		.enumerate()
-		.filter_map(|(time_offset, &bus_id)| bus_id.map(move |bus_id| (time_offset, bus_id)))
-		.max_by_key(|&(_, bus_id)| bus_id);
-	let (time_offset_of_max_bus_id, max_bus_id) =
-		if let Some(max_bus_id_parameters) = max_bus_id_parameters {
-			max_bus_id_parameters
-		}
-		else {
-			// All buses are "x", so time 0 is valid.
-			return Ok(0);
-		};
-
-	let mut times =
-		(0..)
-		.filter_map(|time| (time * max_bus_id).checked_sub(time_offset_of_max_bus_id));
+fn part2(bus_ids: &[Option<usize>]) -> Result<u128, super::Error> {
+	let schedule = Schedule::new(bus_ids);
+	let (time, _) = schedule.earliest_departure_with_offsets(&schedule.buses)?;
+	Ok(time)
+}
 
-	Ok(times.find(|&time| check_part2(time, bus_ids)).ok_or("no solution")?)
+#[derive(Debug)]
+struct Schedule {
+	/// The ID of each bus, and its offset in the list of buses.
+	buses: Vec<(usize, usize)>,
 }
 
-#[cfg(not(test))]
-fn part2(bus_ids: &[Option<usize>]) -> String {
-	// Dammit, I signed up for Advent of Code, not Project Euler.
+impl Schedule {
+	fn new(bus_ids: &[Option<usize>]) -> Self {
+		let buses =
+			bus_ids.iter()
+			.enumerate()
+			.filter_map(|(time_offset, &bus_id)| bus_id.map(|bus_id| (bus_id, time_offset)))
+			.collect();
+		Schedule { buses }
+	}
 
-	fn join(nums: &[usize]) -> String {
-		let mut result = String::new();
+	/// The ID of each bus in the order they're first listed. Buses that are listed more than once are only returned once.
+	fn bus_ids(&self) -> impl Iterator<Item = usize> + '_ {
+		let mut seen = std::collections::BTreeSet::new();
+		self.buses.iter().map(|&(bus_id, _)| bus_id).filter(move |&bus_id| seen.insert(bus_id))
+	}
 
-		for num in nums {
-			if !result.is_empty() {
-				result.push_str("%2c+");
+	/// The next `n` times at or after the given time that each bus departs.
+	///
+	/// Returns an error if any of those times does not fit in a `usize`.
+	fn next_departures(&self, time: usize, n: usize) -> Result<Vec<(usize, Vec<usize>)>, super::Error> {
+		self.bus_ids()
+		.map(|bus_id| {
+			let departures =
+				std::iter::successors(next_departure(time, bus_id), |departure| departure.checked_add(bus_id))
+				.take(n)
+				.collect::<Vec<_>>();
+			if departures.len() < n {
+				return Err(format!("bus {bus_id} does not depart {n} times at or after {time}").into());
 			}
-			result.push_str(&num.to_string());
-		}
+			Ok((bus_id, departures))
+		})
+		.collect()
+	}
 
+	/// Every departure in the given range of times, ordered by time and then by bus ID.
+	fn departures_between(&self, times: std::ops::Range<usize>) -> Vec<(usize, usize)> {
+		let mut result: Vec<_> =
+			self.bus_ids()
+			.flat_map(|bus_id| {
+				// If the next departure doesn't fit in a usize, it's after the end of the range.
+				let next_departure = next_departure(times.start, bus_id).unwrap_or(times.end);
+				(next_departure..times.end).step_by(bus_id).map(move |time| (time, bus_id))
+			})
+			.collect();
+		result.sort_unstable();
 		result
 	}
 
-	// Find time such that:
-	//    (time + time_offset) % bus_id = 0
-	// => (time + time_offset % bus_id) % bus_id = 0    (since time_offset may be greater than bus_id)
-	// => time % bus_id = (bus_id - (time_offset % bus_id)) % bus_id
-	let (divisors, remainders): (Vec<_>, Vec<_>) =
-		bus_ids.iter()
-		.enumerate()
-		.filter_map(|(time_offset, &bus_id)| bus_id.map(|bus_id| (bus_id, ((bus_id - time_offset % bus_id) % bus_id))))
-		.unzip();
-
-	format!(
-		"https://www.wolframalpha.com/input/?i=ChineseRemainder%28%7b{}%7d%2c+%7b{}%7d%29",
-		join(&remainders),
-		join(&divisors),
-	)
+	/// The earliest time such that each of the given buses departs at the given offset after it,
+	/// and the period after which that happens again.
+	fn earliest_departure_with_offsets(&self, buses: &[(usize, usize)]) -> Result<(u128, u128), super::Error> {
+		let congruences: Vec<_> =
+			buses.iter()
+			.map(|&(bus_id, time_offset)| {
+				if !self.buses.iter().any(|&(scheduled_bus_id, _)| scheduled_bus_id == bus_id) {
+					return Err(format!("bus {bus_id} is not in the schedule"));
+				}
+
+				// Find time such that:
+				//    (time + time_offset) % bus_id = 0
+				// => (time + time_offset % bus_id) % bus_id = 0    (since time_offset may be greater than bus_id)
+				// => time % bus_id = (bus_id - (time_offset % bus_id)) % bus_id
+				Ok(((bus_id - time_offset % bus_id) % bus_id, bus_id))
+			})
+			.map(|congruence| congruence.map(|(remainder, modulus)| (remainder as u128, modulus as u128)))
+			.collect::<Result<_, _>>()?;
+
+		let result = chinese_remainder(congruences)?.ok_or("no solution")?;
+		Ok(result)
+	}
+
+	/// The period after which every bus departs at the same times again, ie the LCM of the bus IDs.
+	fn period(&self) -> Result<u128, super::Error> {
+		let (_, period) = self.earliest_departure_with_offsets(&self.buses.iter().map(|&(bus_id, _)| (bus_id, 0)).collect::<Vec<_>>())?;
+		Ok(period)
+	}
+}
+
+/// The first time at or after the given time that the given bus departs, or `None` if it does not fit in a `usize`.
+fn next_departure(time: usize, bus_id: usize) -> Option<usize> {
+	match time % bus_id {
+		0 => Some(time),
+		remainder => time.checked_add(bus_id - remainder),
+	}
 }
 
-fn check_part2(time: usize, bus_ids: &[Option<usize>]) -> bool {
+/// Solves the system of congruences `x % modulus = remainder`, and returns the smallest solution and the period of the solutions,
+/// ie the LCM of the moduli. Returns `None` if there is no solution, which can happen if the moduli are not pairwise coprime.
+///
+/// Returns an error if any modulus is 0 or does not fit in a `u64`, or if the LCM of the moduli does not fit in a `u128`.
+fn chinese_remainder(congruences: impl IntoIterator<Item = (u128, u128)>) -> Result<Option<(u128, u128)>, super::Error> {
+	let mut result = (0, 1);
+
+	for (remainder, modulus) in congruences {
+		if modulus == 0 || modulus > u128::from(u64::MAX) {
+			return Err(format!("invalid modulus {modulus}").into());
+		}
+		let remainder = remainder % modulus;
+
+		let (x, lcm) = result;
+
+		// Find k such that (x + lcm * k) % modulus = remainder
+		// => (lcm * k) % modulus = (remainder - x) % modulus
+		//
+		// This has a solution iff gcd(lcm, modulus) divides (remainder - x), in which case dividing everything by the gcd gives
+		// k % (modulus / gcd) = ((remainder - x) / gcd) * inverse(lcm / gcd) % (modulus / gcd)
+		let (gcd, lcm_inverse) = gcd_and_inverse(lcm % modulus, modulus);
+
+		let (difference, difference_is_negative) = {
+			let x = x % modulus;
+			if remainder >= x { (remainder - x, false) } else { (x - remainder, true) }
+		};
+		if difference % gcd != 0 {
+			return Ok(None);
+		}
+
+		let reduced_modulus = modulus / gcd;
+		let mut difference = difference / gcd % reduced_modulus;
+		if difference_is_negative {
+			difference = (reduced_modulus - difference) % reduced_modulus;
+		}
+
+		// Both factors are less than the modulus, which fits in a u64, so this doesn't overflow.
+		let k = difference * lcm_inverse % reduced_modulus;
+
+		let new_lcm = lcm.checked_mul(reduced_modulus).ok_or("LCM of moduli is too large")?;
+		// lcm * k + x < lcm * (k + 1) <= lcm * reduced_modulus = new_lcm, so this doesn't overflow either.
+		result = (lcm * k + x, new_lcm);
+	}
+
+	Ok(Some(result))
+}
+
+/// Returns `gcd(a, m)` and the inverse of `a / gcd` modulo `m / gcd`.
+fn gcd_and_inverse(a: u128, m: u128) -> (u128, u128) {
+	// Extended Euclidean algorithm. a and m fit in a u64, so the coefficients fit in an i128.
+	let (mut old_r, mut r) = (i128::try_from(a).expect("a fits in a u64"), i128::try_from(m).expect("m fits in a u64"));
+	let (mut old_s, mut s) = (1_i128, 0_i128);
+
+	while r != 0 {
+		let quotient = old_r / r;
+		(old_r, r) = (r, old_r - quotient * r);
+		(old_s, s) = (s, old_s - quotient * s);
+	}
+
+	let gcd = old_r.unsigned_abs();
+	let reduced_modulus = i128::try_from(m / gcd).expect("m fits in a u64");
+	let inverse = old_s.rem_euclid(reduced_modulus).unsigned_abs();
+	(gcd, inverse)
+}
+
+fn check_part2(time: u128, bus_ids: &[Option<usize>]) -> bool {
 	bus_ids.iter()
 	.enumerate()
-	.all(|(i, &bus_id)| bus_id.map_or(true, |bus_id| (time + i) % bus_id == 0))
+	.all(|(i, &bus_id)| bus_id.map_or(true, |bus_id| (time + i as u128) % bus_id as u128 == 0))
 }
 
 #[cfg(test)]
@@ -145,7 +258,83 @@ mod tests {
 
 	#[test]
 	fn part2() {
-		let (_, bus_ids) = super::parse_input(INPUT.split('\n').map(Ok)).unwrap();
-		assert_eq!(super::part2(&bus_ids).unwrap(), 1068781);
+		for &(input, expected) in &[
+			(INPUT, 1068781),
+			("0\n17,x,13,19", 3417),
+			("0\n67,7,59,61", 754018),
+			("0\n67,x,7,59,61", 779210),
+			("0\n67,7,x,59,61", 1261476),
+			("0\n1789,37,47,1889", 1202161486),
+			("0\nx,x", 0),
+		] {
+			let (_, bus_ids) = super::parse_input(input.split('\n').map(Ok)).unwrap();
+			assert_eq!(super::part2(&bus_ids).unwrap(), expected);
+		}
+
+		// Buses 4 and 6 both leave at even times, so they can't leave one minute apart.
+		let (_, bus_ids) = super::parse_input("0\n4,6".split('\n').map(Ok)).unwrap();
+		assert!(super::part2(&bus_ids).is_err());
+
+		let (_, bus_ids) = super::parse_input("0\n4,x,6".split('\n').map(Ok)).unwrap();
+		assert_eq!(super::part2(&bus_ids).unwrap(), 4);
+
+		assert!(super::parse_input("0\n4,0".split('\n').map(Ok)).is_err());
+	}
+
+	#[test]
+	fn chinese_remainder() {
+		assert_eq!(super::chinese_remainder(vec![(2, 3), (3, 5), (2, 7)]).unwrap(), Some((23, 105)));
+		assert_eq!(super::chinese_remainder(vec![(0, 4), (4, 6)]).unwrap(), Some((4, 12)));
+		assert_eq!(super::chinese_remainder(vec![(0, 4), (5, 6)]).unwrap(), None);
+		assert_eq!(super::chinese_remainder(vec![]).unwrap(), Some((0, 1)));
+		assert_eq!(super::chinese_remainder(vec![(7, 5)]).unwrap(), Some((2, 5)));
+		assert!(super::chinese_remainder(vec![(0, 0)]).is_err());
+
+		// Large coprime moduli whose product needs more than 64 bits.
+		let (x, lcm) = super::chinese_remainder(vec![(1, u128::from(u64::MAX)), (2, u128::from(u64::MAX - 1))]).unwrap().unwrap();
+		assert_eq!(lcm, u128::from(u64::MAX) * u128::from(u64::MAX - 1));
+		assert_eq!((x % u128::from(u64::MAX), x % u128::from(u64::MAX - 1)), (1, 2));
+
+		assert!(super::chinese_remainder(vec![(0, u128::from(u64::MAX)), (0, u128::from(u64::MAX - 1)), (0, u128::from(u64::MAX - 2))]).is_err());
+	}
+
+	#[test]
+	fn schedule() {
+		let (earliest_departure_timestamp, bus_ids) = super::parse_input(INPUT.split('\n').map(Ok)).unwrap();
+		let schedule = super::Schedule::new(&bus_ids);
+
+		assert_eq!(schedule.next_departures(earliest_departure_timestamp, 2).unwrap(), [
+			(7, vec![945, 952]),
+			(13, vec![949, 962]),
+			(59, vec![944, 1003]),
+			(31, vec![961, 992]),
+			(19, vec![950, 969]),
+		]);
+
+		assert_eq!(schedule.departures_between(939..950), [(944, 59), (945, 7), (949, 13)]);
+		assert_eq!(schedule.departures_between(0..1), [(0, 7), (0, 13), (0, 19), (0, 31), (0, 59)]);
+
+		assert_eq!(schedule.earliest_departure_with_offsets(&[(7, 0), (13, 1)]).unwrap(), (77, 91));
+		assert_eq!(schedule.earliest_departure_with_offsets(&[(59, 4), (7, 0)]).unwrap(), (350, 413));
+		assert!(schedule.earliest_departure_with_offsets(&[(5, 0)]).is_err());
+
+		assert_eq!(schedule.period().unwrap(), 7 * 13 * 59 * 31 * 19);
+
+		let schedule = super::Schedule::new(&[Some(4), Some(6)]);
+		assert_eq!(schedule.period().unwrap(), 12);
+		assert!(schedule.earliest_departure_with_offsets(&[(4, 0), (6, 1)]).is_err());
+
+		// Buses that are listed more than once are only listed once in the timetable.
+		let schedule = super::Schedule::new(&[Some(7), None, Some(3), Some(7)]);
+		assert_eq!(schedule.next_departures(1, 2).unwrap(), [(7, vec![7, 14]), (3, vec![3, 6])]);
+		assert_eq!(schedule.departures_between(0..8), [(0, 3), (0, 7), (3, 3), (6, 3), (7, 7)]);
+
+		// usize::MAX - 1 is a multiple of 7, but the next departure after it is not a usize.
+		let schedule = super::Schedule::new(&[Some(7)]);
+		assert_eq!(schedule.next_departures(usize::MAX - 1, 1).unwrap(), [(7, vec![usize::MAX - 1])]);
+		assert!(schedule.next_departures(usize::MAX - 1, 2).is_err());
+		assert!(schedule.next_departures(usize::MAX, 1).is_err());
+		assert_eq!(schedule.departures_between((usize::MAX - 10)..usize::MAX), [(usize::MAX - 8, 7), (usize::MAX - 1, 7)]);
+		assert_eq!(schedule.departures_between(usize::MAX..usize::MAX), []);
 	}
 }
@@ -9,34 +9,48 @@ pub(super) fn run() -> Result<(), super::Error> {
 		println!("3a: {result}");
 
 		assert_eq!(result, 187);
+
+		assert_eq!(trees.render_path(1, 3).matches('X').count(), result);
 	}
 
 	{
-		let down_1_right_1 = trees.count_along_diagonal(1, 1);
-		let down_1_right_5 = trees.count_along_diagonal(1, 5);
-		let down_1_right_7 = trees.count_along_diagonal(1, 7);
-		let down_2_right_1 = trees.count_along_diagonal(2, 1);
+		let (best, worst) = trees.best_and_worst_slopes(1..=2, 1..=7).ok_or("no slopes")?;
+		assert_eq!((best.down, best.right, best.num_trees), (2, 3, 41));
+		assert_eq!((worst.down, worst.right, worst.num_trees), (1, 3, 187));
+	}
 
-		let result = down_1_right_1 * down_1_right_3 * down_1_right_5 * down_1_right_7 * down_2_right_1;
+	{
+		let result = trees.product_along_diagonals(SLOPES.iter().copied());
 
 		println!("3b: {result}");
 
 		assert_eq!(result, 4723283400);
+
+		for &(down, right) in &SLOPES {
+			assert_eq!(trees.count_along_fractional_diagonal(down, right), trees.count_along_diagonal(down, right));
+		}
 	}
 
 	Ok(())
 }
 
+const SLOPES: [(usize, usize); 5] = [(1, 1), (1, 3), (1, 5), (1, 7), (2, 1)];
+
 #[derive(Debug)]
 struct Trees {
-	map: std::collections::BTreeSet<(usize, usize)>,
+	// Row-major bitmap with each row padded to a whole number of words, so bit `col % 64` of `map[row * words_per_row + col / 64]`
+	// is set iff there's a tree at `(row, col)`.
+	// Much faster to count along than the `std::collections::BTreeSet<(usize, usize)>` it replaced; see `tests::bench_count_along_diagonal`.
+	map: Vec<u64>,
+	words_per_row: usize,
 	num_rows: usize,
 	num_cols: usize,
 }
 
 impl Trees {
 	fn new(input: impl Iterator<Item = Result<impl AsRef<str>, super::Error>>) -> Result<Self, super::Error> {
-		let mut map: std::collections::BTreeSet<(usize, usize)> = Default::default();
+		let mut map = vec![];
+		let mut words_per_row = 0;
 		let mut num_rows = 0;
 		let mut num_cols = 0;
 
@@ -46,29 +60,149 @@ impl Trees {
 
 			if row == 0 {
 				num_cols = line.len();
+				if num_cols == 0 {
+					return Err("first row of map is empty".into());
+				}
+				words_per_row = (num_cols + 63) / 64;
+			}
+			else if line.len() != num_cols {
+				return Err(format!("row {row} of map has {} cols but row 0 has {num_cols} cols", line.len()).into());
 			}
 
-			for (col, c) in line.chars().enumerate() {
-				if c == '#' {
-					map.insert((row, col));
+			map.resize(map.len() + words_per_row, 0);
+			let words = &mut map[(row * words_per_row)..];
+
+			for (col, c) in line.bytes().enumerate() {
+				match c {
+					b'#' => words[col / 64] |= 1 << (col % 64),
+					b'.' => (),
+					c => return Err(format!("row {row} col {col} of map has invalid cell {:?}", char::from(c)).into()),
 				}
 			}
 
 			num_rows += 1;
 		}
 
+		if num_rows == 0 {
+			return Err("map is empty".into());
+		}
+
 		Ok(Trees {
 			map,
+			words_per_row,
 			num_rows,
 			num_cols,
 		})
 	}
 
+	fn contains(&self, row: usize, col: usize) -> bool {
+		(self.map[row * self.words_per_row + col / 64] >> (col % 64)) & 1 == 1
+	}
+
+	/// Starting from the top-left, repeatedly moves `down` rows and `right` cols until past the last row,
+	/// and counts the trees that were landed on.
+	///
+	/// `down` must be non-zero.
 	fn count_along_diagonal(&self, down: usize, right: usize) -> usize {
-		(0..(self.num_rows / down))
-			.filter(|&row| self.map.contains(&(row * down, (row * right) % self.num_cols)))
+		self.path_along_diagonal(down, right).filter(|step| step.hit).count()
+	}
+
+	/// Like [`Trees::count_along_diagonal`], but treats `right / down` as the rational slope of a line through the top-left cell,
+	/// and counts the trees on every cell the line passes through exactly.
+	///
+	/// This differs from `count_along_diagonal` when `down` and `right` are not coprime. For example, `down = 4, right = 2`
+	/// visits every second row (one col to the right each time), whereas `count_along_diagonal` would visit every fourth row.
+	///
+	/// `down` must be non-zero.
+	fn count_along_fractional_diagonal(&self, down: usize, right: usize) -> usize {
+		(0..self.num_rows)
+			.filter(|&row| (row * right) % down == 0)
+			.filter(|&row| self.contains(row, ((row * right) / down) % self.num_cols))
 			.count()
 	}
+
+	fn product_along_diagonals(&self, slopes: impl IntoIterator<Item = (usize, usize)>) -> usize {
+		slopes.into_iter().map(|(down, right)| self.count_along_diagonal(down, right)).product()
+	}
+
+	/// Evaluates every slope with `down` in `downs` and `right` in `rights`, in that order.
+	///
+	/// Slopes with `down` of 0 never leave the first row, so they are skipped.
+	fn slopes_within(
+		&self,
+		downs: std::ops::RangeInclusive<usize>,
+		rights: std::ops::RangeInclusive<usize>,
+	) -> impl Iterator<Item = Slope> + '_ {
+		downs.filter(|&down| down > 0).flat_map(move |down| rights.clone().map(move |right| Slope {
+			down,
+			right,
+			num_trees: self.count_along_diagonal(down, right),
+		}))
+	}
+
+	/// Returns the slopes within the given bounds that hit the fewest and the most trees respectively.
+	///
+	/// Ties are broken in favor of the first slope, as ordered by [`Trees::slopes_within`].
+	fn best_and_worst_slopes(
+		&self,
+		downs: std::ops::RangeInclusive<usize>,
+		rights: std::ops::RangeInclusive<usize>,
+	) -> Option<(Slope, Slope)> {
+		self.slopes_within(downs, rights).fold(None, |extremes, slope| Some(match extremes {
+			Some((best, worst)) => (
+				if slope.num_trees < best.num_trees { slope } else { best },
+				if slope.num_trees > worst.num_trees { slope } else { worst },
+			),
+			None => (slope, slope),
+		}))
+	}
+
+	fn path_along_diagonal(&self, down: usize, right: usize) -> impl Iterator<Item = PathStep> + '_ {
+		(0..self.num_rows)
+			.step_by(down)
+			.enumerate()
+			.map(move |(step, row)| {
+				let col = (step * right) % self.num_cols;
+				PathStep {
+					row,
+					col,
+					hit: self.contains(row, col),
+				}
+			})
+	}
+
+	/// Renders the map with the cells visited along the given slope marked with `X` if they have a tree and `O` if they don't.
+	fn render_path(&self, down: usize, right: usize) -> String {
+		let mut rows: Vec<Vec<u8>> =
+			(0..self.num_rows)
+			.map(|row| (0..self.num_cols).map(|col| if self.contains(row, col) { b'#' } else { b'.' }).collect())
+			.collect();
+
+		for PathStep { row, col, hit } in self.path_along_diagonal(down, right) {
+			rows[row][col] = if hit { b'X' } else { b'O' };
+		}
+
+		let mut result = String::with_capacity(self.num_rows * (self.num_cols + 1));
+		for row in rows {
+			result.push_str(std::str::from_utf8(&row).expect("map only contains ASCII"));
+			result.push('\n');
+		}
+		result
+	}
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Slope {
+	down: usize,
+	right: usize,
+	num_trees: usize,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct PathStep {
+	row: usize,
+	col: usize,
+	hit: bool,
 }
 
 #[cfg(test)]
@@ -108,5 +242,112 @@ mod tests {
 
 		let result = down_1_right_1 * down_1_right_3 * down_1_right_5 * down_1_right_7 * down_2_right_1;
 		assert_eq!(result, 2 * 7 * 3 * 4 * 2);
+
+		assert_eq!(trees.product_along_diagonals(super::SLOPES.iter().copied()), result);
+	}
+
+	#[test]
+	fn count_along_fractional_diagonal() {
+		let trees = super::Trees::new(INPUT.lines().map(Ok)).unwrap();
+
+		assert_eq!(trees.count_along_fractional_diagonal(1, 3), trees.count_along_diagonal(1, 3));
+		assert_eq!(trees.count_along_fractional_diagonal(4, 2), trees.count_along_diagonal(2, 1));
+		assert_eq!(trees.count_along_fractional_diagonal(3, 2), 1);
+		assert_eq!(trees.count_along_fractional_diagonal(2, 3), 2);
+	}
+
+	#[test]
+	fn invalid_map() {
+		assert!(super::Trees::new(std::iter::empty::<Result<&str, _>>()).is_err());
+		assert!(super::Trees::new(["..#", ".#"].iter().map(Ok)).is_err());
+		assert!(super::Trees::new(["..#", ".O."].iter().map(Ok)).is_err());
+	}
+
+	#[test]
+	fn slopes_within() {
+		let trees = super::Trees::new(INPUT.lines().map(Ok)).unwrap();
+
+		assert_eq!(trees.slopes_within(1..=2, 0..=7).count(), 16);
+		assert_eq!(trees.slopes_within(0..=2, 0..=7).count(), 16);
+		assert_eq!(trees.slopes_within(0..=0, 0..=7).count(), 0);
+		assert_eq!(trees.best_and_worst_slopes(0..=0, 1..=7), None);
+
+		let (best, worst) = trees.best_and_worst_slopes(1..=1, 1..=7).unwrap();
+		assert_eq!(best, super::Slope { down: 1, right: 2, num_trees: 1 });
+		assert_eq!(worst, super::Slope { down: 1, right: 3, num_trees: 7 });
+	}
+
+	#[test]
+	fn render_path() {
+		let trees = super::Trees::new(INPUT.lines().map(Ok)).unwrap();
+
+		assert_eq!(trees.path_along_diagonal(1, 3).filter(|step| step.hit).count(), 7);
+
+		assert_eq!(trees.render_path(1, 3), "\
+O.##.......
+#..O#...#..
+.#....X..#.
+..#.#...#O#
+.X...##..#.
+..#.X#.....
+.#.#.#.O..#
+.#........X
+#.X#...#...
+#...#X....#
+.#..#...X.#
+");
+	}
+
+	#[test]
+	#[ignore = "benchmark"]
+	fn bench_count_along_diagonal() {
+		// The `Trees` representation before it was changed to a bitmap.
+		struct BTreeSetTrees {
+			map: std::collections::BTreeSet<(usize, usize)>,
+			num_rows: usize,
+			num_cols: usize,
+		}
+
+		impl BTreeSetTrees {
+			fn count_along_diagonal(&self, down: usize, right: usize) -> usize {
+				(0..self.num_rows)
+					.step_by(down)
+					.enumerate()
+					.filter(|&(step, row)| self.map.contains(&(row, (step * right) % self.num_cols)))
+					.count()
+			}
+		}
+
+		const NUM_ROWS: usize = 100_000;
+		const NUM_COLS: usize = 1_000;
+
+		let mut random = super::super::random_numbers();
+		let input: Vec<String> =
+			(0..NUM_ROWS)
+			.map(|_| (0..NUM_COLS).map(|_| if random() % 4 == 0 { '#' } else { '.' }).collect())
+			.collect();
+
+		let btreeset_trees = BTreeSetTrees {
+			map:
+				input.iter().enumerate()
+				.flat_map(|(row, line)| line.bytes().enumerate().filter(|&(_, c)| c == b'#').map(move |(col, _)| (row, col)))
+				.collect(),
+			num_rows: NUM_ROWS,
+			num_cols: NUM_COLS,
+		};
+
+		let trees = super::Trees::new(input.iter().map(Ok)).unwrap();
+
+		let start = std::time::Instant::now();
+		let expected: Vec<_> = super::SLOPES.iter().map(|&(down, right)| btreeset_trees.count_along_diagonal(down, right)).collect();
+		let btreeset_elapsed = start.elapsed();
+
+		let start = std::time::Instant::now();
+		let actual: Vec<_> = super::SLOPES.iter().map(|&(down, right)| trees.count_along_diagonal(down, right)).collect();
+		let bitmap_elapsed = start.elapsed();
+
+		assert_eq!(actual, expected);
+
+		println!("BTreeSet: {btreeset_elapsed:?}, bitmap: {bitmap_elapsed:?}");
 	}
 }
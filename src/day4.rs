@@ -1,8 +1,17 @@
 pub(super) fn run() -> Result<(), super::Error> {
+	let schema = Schema::parse(super::read_input_lines::<String>("day4-schema")?)?;
+
 	let passports = Passport::parse(super::read_input_lines::<String>("day4")?)?;
 
+	assert!(passports.iter().all(|passport| schema.warnings(passport).is_empty()));
+	assert_eq!(Passport::parse(Passport::to_batch(&passports).lines().map(Ok))?, passports);
+	assert_eq!(
+		Passport::to_json(&passports).matches("\"key\":").count(),
+		passports.iter().map(|passport| passport.fields.len()).sum::<usize>(),
+	);
+
 	{
-		let result = passports.iter().filter(|passport| passport.is_valid1()).count();
+		let result = passports.iter().filter(|passport| schema.has_required_fields(passport)).count();
 
 		println!("4a: {result}");
 
@@ -10,7 +19,7 @@ pub(super) fn run() -> Result<(), super::Error> {
 	}
 
 	{
-		let result = passports.iter().filter(|passport| passport.is_valid2()).count();
+		let result = passports.iter().filter(|passport| schema.validate(passport).is_empty()).count();
 
 		println!("4b: {result}");
 
@@ -20,16 +29,25 @@ pub(super) fn run() -> Result<(), super::Error> {
 	Ok(())
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Eq, PartialEq)]
 struct Passport {
-	byr: Option<String>,
-	cid: Option<String>,
-	ecl: Option<String>,
-	eyr: Option<String>,
-	hcl: Option<String>,
-	hgt: Option<String>,
-	iyr: Option<String>,
-	pid: Option<String>,
+	/// All the fields of the passport in the order they appeared in, including duplicates.
+	fields: Vec<Field>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+struct Field {
+	key: String,
+	value: String,
+
+	/// The 1-based line number of the input that this field was on.
+	line: usize,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum Warning {
+	Duplicate { key: String, first_line: usize, line: usize },
+	Unknown { key: String, line: usize },
 }
 
 impl Passport {
@@ -37,149 +55,344 @@ impl Passport {
 		let mut passports = vec![];
 
 		let mut passport: Passport = Default::default();
-		for line in input {
+		for (line_number, line) in input.enumerate() {
 			let line = line?;
 			let line = line.as_ref();
 
-			if line.is_empty() {
-				passports.push(passport);
-				passport = Default::default();
+			let mut fields = line.split_whitespace().peekable();
+
+			if fields.peek().is_none() {
+				if !passport.fields.is_empty() {
+					passports.push(std::mem::take(&mut passport));
+				}
 			}
 			else {
-				for field in line.split(' ') {
-					let mut parts = field.split(':');
-
-					let key = parts.next().expect("str::split yields at least one part");
-					let value = parts.next().ok_or_else(|| format!("malformed line {line:?}"))?;
-
-					match key {
-						"byr" => passport.byr = Some(value.to_owned()),
-						"cid" => passport.cid = Some(value.to_owned()),
-						"ecl" => passport.ecl = Some(value.to_owned()),
-						"eyr" => passport.eyr = Some(value.to_owned()),
-						"hcl" => passport.hcl = Some(value.to_owned()),
-						"hgt" => passport.hgt = Some(value.to_owned()),
-						"iyr" => passport.iyr = Some(value.to_owned()),
-						"pid" => passport.pid = Some(value.to_owned()),
-						_ => return Err(format!("malformed line {line:?}").into()),
-					}
+				for field in fields {
+					let (key, value) = field.split_once(':').ok_or_else(|| format!("line {} has malformed field {field:?}", line_number + 1))?;
+					passport.fields.push(Field {
+						key: key.to_owned(),
+						value: value.to_owned(),
+						line: line_number + 1,
+					});
 				}
 			}
 		}
-		passports.push(passport);
+		if !passport.fields.is_empty() {
+			passports.push(passport);
+		}
 
 		Ok(passports)
 	}
 
-	fn is_valid1(&self) -> bool {
-		let Passport { byr, cid: _, ecl, eyr, hcl, hgt, iyr, pid } = self;
-		byr.as_ref()
-			.and(ecl.as_ref())
-			.and(eyr.as_ref())
-			.and(hcl.as_ref())
-			.and(hgt.as_ref())
-			.and(iyr.as_ref())
-			.and(pid.as_ref())
-			.is_some()
+	/// Returns the value of the last field with the given key.
+	fn get(&self, key: &str) -> Option<&str> {
+		self.fields.iter().rev().find(|field| field.key == key).map(|field| &*field.value)
 	}
 
-	fn is_valid2(&self) -> bool {
-		fn r#catch(f: impl FnOnce() -> Option<()>) -> bool {
-			f().is_some()
-		}
-
-		r#catch(|| {
-			let Passport { byr, cid: _, ecl, eyr, hcl, hgt, iyr, pid } = self;
-
-			{
-				let byr = byr.as_deref()?;
-				let byr: u16 = byr.parse().ok()?;
-				if !(1920..=2002).contains(&byr) {
-					return None;
+	/// Serializes the passports in the puzzle input format, with each field on the line it was parsed from,
+	/// so that parsing the result gives back the same passports.
+	///
+	/// Whitespace within lines is not preserved. The fields on each line are separated by single spaces, and blank lines are empty.
+	fn to_batch(passports: &[Self]) -> String {
+		let mut result = String::new();
+
+		// The 1-based number of the line that `result` ends on, and whether any field has been written to it.
+		let mut line = 1;
+		let mut line_has_fields = false;
+
+		for (i, passport) in passports.iter().enumerate() {
+			for (j, field) in passport.fields.iter().enumerate() {
+				// Passports must be separated by a blank line, even if the fields' lines don't leave room for one.
+				let field_line = if i > 0 && j == 0 { field.line.max(line + 2) } else { field.line.max(line) };
+				while line < field_line {
+					result.push('\n');
+					line += 1;
+					line_has_fields = false;
 				}
-			}
 
-			{
-				let ecl = ecl.as_deref()?;
-				if !matches!(ecl, "amb" | "blu" | "brn" | "gry" | "grn" | "hzl" | "oth") {
-					return None;
+				if line_has_fields {
+					result.push(' ');
 				}
+				result.push_str(&field.key);
+				result.push(':');
+				result.push_str(&field.value);
+				line_has_fields = true;
 			}
+		}
 
-			{
-				let eyr = eyr.as_deref()?;
-				let eyr: u16 = eyr.parse().ok()?;
-				if !(2020..=2030).contains(&eyr) {
-					return None;
-				}
+		if line_has_fields {
+			result.push('\n');
+		}
+
+		result
+	}
+
+	/// Serializes the passports as a JSON array of passports, where each passport is an array of `{ "key", "value", "line" }` objects.
+	fn to_json(passports: &[Self]) -> String {
+		use std::fmt::Write;
+
+		let mut result = String::from("[");
+
+		for (i, passport) in passports.iter().enumerate() {
+			if i > 0 {
+				result.push(',');
 			}
 
-			{
-				let hcl = hcl.as_deref()?;
-				let mut hcl = hcl.chars();
-				if hcl.next()? != '#' {
-					return None;
-				}
-				for _ in 0..6 {
-					let c = hcl.next()?;
-					if !('0'..='9').contains(&c) && !('a'..='f').contains(&c) {
-						return None;
-					}
-				}
-				if hcl.next().is_some() {
-					return None;
+			result.push('[');
+			for (j, field) in passport.fields.iter().enumerate() {
+				if j > 0 {
+					result.push(',');
 				}
+
+				result.push_str("{\"key\":");
+				super::push_json_string(&mut result, &field.key);
+				result.push_str(",\"value\":");
+				super::push_json_string(&mut result, &field.value);
+				write!(result, ",\"line\":{}}}", field.line).expect("cannot fail to write to String");
 			}
+			result.push(']');
+		}
 
-			{
-				let hgt = hgt.as_deref()?;
-				if let Some(hgt) = hgt.strip_suffix("cm") {
-					let hgt: u8 = hgt.parse().ok()?;
-					if !(150..=193).contains(&hgt) {
-						return None;
+		result.push(']');
+		result
+	}
+}
+
+/// The fields a passport may have, and the rules their values must satisfy.
+///
+/// Each line of the schema is `<key> required|optional <rule>`, where `<rule>` is one of:
+///
+/// - `any`
+/// - `year <min> <max>`: a four-digit year in `min..=max`
+/// - `height <unit> <min> <max> ...`: a number immediately followed by one of the units, in that unit's range
+/// - `color`: `#` followed by six lowercase hex digits
+/// - `enum <value> ...`: one of the values
+/// - `digits <n>`: exactly `n` decimal digits
+#[derive(Debug)]
+struct Schema {
+	fields: Vec<FieldSchema>,
+}
+
+#[derive(Debug)]
+struct FieldSchema {
+	key: String,
+	required: bool,
+	rule: Rule,
+}
+
+#[derive(Debug)]
+enum Rule {
+	Any,
+	Year(std::ops::RangeInclusive<u16>),
+	Height(Vec<(String, std::ops::RangeInclusive<u16>)>),
+	Color,
+	Enum(Vec<String>),
+	Digits(usize),
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum FieldError {
+	Missing,
+	NotANumber(String),
+	OutOfRange(String, std::ops::RangeInclusive<u16>),
+	UnknownUnit(String),
+	NotAColor(String),
+	NotInEnum(String),
+	WrongNumDigits(String, usize),
+}
+
+impl std::fmt::Display for FieldError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			FieldError::Missing => f.write_str("required field is missing"),
+			FieldError::NotANumber(value) => write!(f, "{value:?} is not a number"),
+			FieldError::OutOfRange(value, range) => write!(f, "{value} is not in the range {}..={}", range.start(), range.end()),
+			FieldError::UnknownUnit(value) => write!(f, "{value:?} does not have a valid unit"),
+			FieldError::NotAColor(value) => write!(f, "{value:?} is not a color"),
+			FieldError::NotInEnum(value) => write!(f, "{value:?} is not one of the allowed values"),
+			FieldError::WrongNumDigits(value, expected) => write!(f, "{value:?} is not a {expected}-digit number"),
+		}
+	}
+}
+
+impl Schema {
+	fn parse(input: impl Iterator<Item = Result<impl AsRef<str>, super::Error>>) -> Result<Self, super::Error> {
+		fn parse_range<'a>(parts: &mut impl Iterator<Item = &'a str>, line: &str) -> Result<std::ops::RangeInclusive<u16>, super::Error> {
+			let min = parts.next().ok_or_else(|| format!("malformed schema line {line:?}"))?.parse()?;
+			let max = parts.next().ok_or_else(|| format!("malformed schema line {line:?}"))?.parse()?;
+			Ok(min..=max)
+		}
+
+		let mut fields = vec![];
+
+		for line in input {
+			let line = line?;
+			let line = line.as_ref();
+
+			let mut parts = line.split_whitespace();
+
+			let key = match parts.next() {
+				Some(key) => key.to_owned(),
+				None => continue,
+			};
+
+			let required = match parts.next() {
+				Some("required") => true,
+				Some("optional") => false,
+				_ => return Err(format!("malformed schema line {line:?}").into()),
+			};
+
+			let rule = match parts.next() {
+				Some("any") => Rule::Any,
+
+				Some("year") => Rule::Year(parse_range(&mut parts, line)?),
+
+				Some("height") => {
+					let mut units = vec![];
+					while let Some(unit) = parts.next() {
+						units.push((unit.to_owned(), parse_range(&mut parts, line)?));
 					}
-				}
-				else if let Some(hgt) = hgt.strip_suffix("in") {
-					let hgt: u8 = hgt.parse().ok()?;
-					if !(59..=76).contains(&hgt) {
-						return None;
+					if units.is_empty() {
+						return Err(format!("malformed schema line {line:?}").into());
 					}
-				}
-				else {
-					return None;
-				}
+					Rule::Height(units)
+				},
+
+				Some("color") => Rule::Color,
+
+				Some("enum") => Rule::Enum(parts.by_ref().map(ToOwned::to_owned).collect()),
+
+				Some("digits") => Rule::Digits(parts.next().ok_or_else(|| format!("malformed schema line {line:?}"))?.parse()?),
+
+				_ => return Err(format!("malformed schema line {line:?}").into()),
+			};
+
+			if parts.next().is_some() {
+				return Err(format!("malformed schema line {line:?}").into());
 			}
 
-			{
-				let iyr = iyr.as_deref()?;
-				let iyr: u16 = iyr.parse().ok()?;
-				if !(2010..=2020).contains(&iyr) {
-					return None;
-				}
+			fields.push(FieldSchema { key, required, rule });
+		}
+
+		Ok(Schema { fields })
+	}
+
+	fn has_required_fields(&self, passport: &Passport) -> bool {
+		self.fields.iter().all(|field| !field.required || passport.get(&field.key).is_some())
+	}
+
+	/// Returns the errors of every field of the passport in schema order. The passport is valid iff this is empty.
+	///
+	/// If a field is duplicated, only its last value is validated. Unknown fields do not make the passport invalid.
+	/// Both are reported by [`Schema::warnings`] instead.
+	fn validate<'a>(&'a self, passport: &'a Passport) -> Vec<(&'a str, FieldError)> {
+		let mut errors = vec![];
+
+		for field in &self.fields {
+			match passport.get(&field.key) {
+				Some(value) =>
+					if let Err(err) = field.rule.validate(value) {
+						errors.push((&*field.key, err));
+					},
+
+				None =>
+					if field.required {
+						errors.push((&*field.key, FieldError::Missing));
+					},
 			}
+		}
 
-			{
-				let pid = pid.as_deref()?;
-				let mut pid = pid.chars();
-				for _ in 0..9 {
-					let c = pid.next()?;
-					if !('0'..='9').contains(&c) {
-						return None;
-					}
+		errors
+	}
+
+	fn warnings(&self, passport: &Passport) -> Vec<Warning> {
+		let mut warnings = vec![];
+
+		for (i, field) in passport.fields.iter().enumerate() {
+			if let Some(first) = passport.fields[..i].iter().find(|first| first.key == field.key) {
+				warnings.push(Warning::Duplicate { key: field.key.clone(), first_line: first.line, line: field.line });
+			}
+
+			if !self.fields.iter().any(|schema_field| schema_field.key == field.key) {
+				warnings.push(Warning::Unknown { key: field.key.clone(), line: field.line });
+			}
+		}
+
+		warnings
+	}
+}
+
+impl Rule {
+	fn validate(&self, value: &str) -> Result<(), FieldError> {
+		fn parse_in_range(value: &str, range: &std::ops::RangeInclusive<u16>) -> Result<(), FieldError> {
+			if value.is_empty() || !value.bytes().all(|c| c.is_ascii_digit()) {
+				return Err(FieldError::NotANumber(value.to_owned()));
+			}
+			// The value is all digits, so it can only fail to parse if it's too large for any range.
+			if !value.parse().map_or(false, |num: u16| range.contains(&num)) {
+				return Err(FieldError::OutOfRange(value.to_owned(), range.clone()));
+			}
+			Ok(())
+		}
+
+		match self {
+			Rule::Any => Ok(()),
+
+			Rule::Year(range) => {
+				if value.len() != 4 {
+					return Err(FieldError::WrongNumDigits(value.to_owned(), 4));
 				}
-				if pid.next().is_some() {
-					return None;
+				parse_in_range(value, range)
+			},
+
+			Rule::Height(units) => {
+				let (num, range) =
+					units.iter()
+					.find_map(|(unit, range)| value.strip_suffix(&**unit).map(|num| (num, range)))
+					.ok_or_else(|| FieldError::UnknownUnit(value.to_owned()))?;
+				parse_in_range(num, range)
+			},
+
+			Rule::Color => {
+				let is_color =
+					value.strip_prefix('#')
+					.map_or(false, |hex| hex.len() == 6 && hex.bytes().all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f')));
+				if !is_color {
+					return Err(FieldError::NotAColor(value.to_owned()));
 				}
-			}
+				Ok(())
+			},
 
+			Rule::Enum(values) => {
+				if !values.iter().any(|allowed| allowed == value) {
+					return Err(FieldError::NotInEnum(value.to_owned()));
+				}
+				Ok(())
+			},
 
-			Some(())
-		})
+			Rule::Digits(n) => {
+				if value.len() != *n || !value.bytes().all(|c| c.is_ascii_digit()) {
+					return Err(FieldError::WrongNumDigits(value.to_owned(), *n));
+				}
+				Ok(())
+			},
+		}
 	}
 }
 
 #[cfg(test)]
 mod tests {
+	const SCHEMA: &str = "\
+byr required year 1920 2002
+iyr required year 2010 2020
+eyr required year 2020 2030
+hgt required height cm 150 193 in 59 76
+hcl required color
+ecl required enum amb blu brn gry grn hzl oth
+pid required digits 9
+cid optional any\
+";
+
 	#[test]
 	fn part1() {
 		const INPUT: &str = "\
@@ -198,8 +411,10 @@ hcl:#cfa07d eyr:2025 pid:166559648
 iyr:2011 ecl:brn hgt:59in
 ";
 
+		let schema = super::Schema::parse(SCHEMA.split('\n').map(Ok)).unwrap();
+
 		let passports = super::Passport::parse(INPUT.split('\n').map(Ok)).unwrap();
-		assert_eq!(passports.iter().filter(|passport| passport.is_valid1()).count(), 2);
+		assert_eq!(passports.iter().filter(|passport| schema.has_required_fields(passport)).count(), 2);
 	}
 
 	#[test]
@@ -235,10 +450,118 @@ eyr:2022
 iyr:2010 hgt:158cm hcl:#b6652a ecl:blu byr:1944 eyr:2021 pid:093154719
 ";
 
-		let passports = super::Passport::parse(INPUT_INVALID.split('\n').map(Ok)).unwrap();
-		assert_eq!(passports.iter().filter(|passport| passport.is_valid2()).count(), 0);
+		let schema = super::Schema::parse(SCHEMA.split('\n').map(Ok)).unwrap();
+
+		let passports = super::Passport::parse(INPUT_INVALID.lines().map(Ok)).unwrap();
+		let errors: Vec<_> = passports.iter().map(|passport| schema.validate(passport)).collect();
+		assert_eq!(errors, [
+			vec![
+				("eyr", super::FieldError::OutOfRange("1972".to_owned(), 2020..=2030)),
+				("hgt", super::FieldError::UnknownUnit("170".to_owned())),
+				("pid", super::FieldError::WrongNumDigits("186cm".to_owned(), 9)),
+			],
+			vec![
+				("eyr", super::FieldError::OutOfRange("1967".to_owned(), 2020..=2030)),
+			],
+			vec![
+				("hcl", super::FieldError::NotAColor("dab227".to_owned())),
+			],
+			vec![
+				("byr", super::FieldError::OutOfRange("2007".to_owned(), 1920..=2002)),
+				("iyr", super::FieldError::OutOfRange("2023".to_owned(), 2010..=2020)),
+				("eyr", super::FieldError::OutOfRange("2038".to_owned(), 2020..=2030)),
+				("hgt", super::FieldError::OutOfRange("59".to_owned(), 150..=193)),
+				("hcl", super::FieldError::NotAColor("74454a".to_owned())),
+				("ecl", super::FieldError::NotInEnum("zzz".to_owned())),
+				("pid", super::FieldError::WrongNumDigits("3556412378".to_owned(), 9)),
+			],
+		]);
 
 		let passports = super::Passport::parse(INPUT_VALID.split('\n').map(Ok)).unwrap();
-		assert_eq!(passports.iter().filter(|passport| passport.is_valid2()).count(), 4);
+		assert_eq!(passports.iter().filter(|passport| schema.validate(passport).is_empty()).count(), 4);
+
+		// Numbers too large to parse are out of range, not malformed.
+		assert_eq!(
+			super::Rule::Height(vec![("cm".to_owned(), 150..=193)]).validate("99999cm"),
+			Err(super::FieldError::OutOfRange("99999".to_owned(), 150..=193)),
+		);
+		assert_eq!(super::Rule::Year(1920..=2002).validate("99999"), Err(super::FieldError::WrongNumDigits("99999".to_owned(), 4)));
+		assert_eq!(
+			super::Rule::Height(vec![("in".to_owned(), 59..=76)]).validate("123456789012345678901234567890in"),
+			Err(super::FieldError::OutOfRange("123456789012345678901234567890".to_owned(), 59..=76)),
+		);
+	}
+
+	#[test]
+	fn parse_lossless() {
+		const INPUT: &str = "\
+ecl:gry\tpid:860033327   eyr:2020
+hcl:#fffffd byr:1937 iyr:2017 cid:147 hgt:183cm ecl:blu
+
+
+foo:bar:baz iyr:2013
+ \t
+hcl:\"x\\
+";
+
+		let schema = super::Schema::parse(SCHEMA.split('\n').map(Ok)).unwrap();
+
+		let passports = super::Passport::parse(INPUT.lines().map(Ok)).unwrap();
+		assert_eq!(passports.len(), 3);
+
+		assert_eq!(passports[0].fields.len(), 9);
+		assert_eq!(passports[0].get("ecl"), Some("blu"));
+		assert_eq!(schema.warnings(&passports[0]), [
+			super::Warning::Duplicate { key: "ecl".to_owned(), first_line: 1, line: 2 },
+		]);
+		assert!(schema.validate(&passports[0]).is_empty());
+
+		assert_eq!(passports[1].get("foo"), Some("bar:baz"));
+		assert_eq!(schema.warnings(&passports[1]), [
+			super::Warning::Unknown { key: "foo".to_owned(), line: 5 },
+		]);
+
+		assert_eq!(super::Passport::to_batch(&passports), "\
+ecl:gry pid:860033327 eyr:2020
+hcl:#fffffd byr:1937 iyr:2017 cid:147 hgt:183cm ecl:blu
+
+
+foo:bar:baz iyr:2013
+
+hcl:\"x\\
+");
+		assert_eq!(super::Passport::parse(super::Passport::to_batch(&passports).lines().map(Ok)).unwrap(), passports);
+
+		assert_eq!(
+			super::Passport::to_json(&passports[1..]),
+			r#"[[{"key":"foo","value":"bar:baz","line":5},{"key":"iyr","value":"2013","line":5}],[{"key":"hcl","value":"\"x\\","line":7}]]"#,
+		);
+
+		let passports = super::Passport::parse(["", "\t", "byr:1937\t\tiyr:2017", "", "", "", "eyr:2020"].iter().map(Ok)).unwrap();
+		assert_eq!(super::Passport::to_batch(&passports), "\n\nbyr:1937 iyr:2017\n\n\n\neyr:2020\n");
+		assert_eq!(super::Passport::parse(super::Passport::to_batch(&passports).lines().map(Ok)).unwrap(), passports);
+
+		// Passports are kept apart even if their fields' lines overlap.
+		let passports = vec![
+			super::Passport { fields: vec![super::Field { key: "byr".to_owned(), value: "1937".to_owned(), line: 1 }] },
+			super::Passport { fields: vec![super::Field { key: "iyr".to_owned(), value: "2017".to_owned(), line: 1 }] },
+		];
+		assert_eq!(super::Passport::to_batch(&passports), "byr:1937\n\niyr:2017\n");
+	}
+
+	#[test]
+	fn schema_parse_errors() {
+		assert!(super::Schema::parse(SCHEMA.split('\n').map(Ok)).is_ok());
+
+		for &line in &[
+			"byr required date 1920 2002",
+			"hgt required height cm 150",
+			"byr required year 1920 2002 2003",
+			"byr requried year 1920 2002",
+			"pid required digits",
+		] {
+			let err = super::Schema::parse(std::iter::once(Ok(line))).unwrap_err();
+			assert_eq!(err.0.to_string(), format!("malformed schema line {line:?}"));
+		}
 	}
 }
@@ -1,24 +1,17 @@
 pub(super) fn run() -> Result<(), super::Error> {
-	let mut plane = [[false; 8]; 128];
+	let mut plane = Plane::new(Geometry::new(7, 3, ('F', 'B'), ('L', 'R'))?);
 
 	for line in super::read_input_lines::<String>("day5")? {
 		let line = line?;
-
-		let (row_num, seat_num) = find_seat(&line)?;
-		plane[row_num][seat_num] = true;
+		let (row_num, seat_num) = plane.board(&line)?;
+		assert_eq!(plane.geometry.encode_seat(row_num, seat_num)?, line);
 	}
 
+	assert_eq!(plane.duplicates().count(), 0);
+	assert_eq!(plane.row_occupancy().sum::<usize>(), plane.render().matches('#').count());
+
 	{
-		let result =
-			plane.iter()
-			.enumerate()
-			.flat_map(|(row_num, row)| {
-				row.iter()
-				.enumerate()
-				.filter_map(move |(seat_num, &occupied)| occupied.then(|| seat_id(row_num, seat_num)))
-			})
-			.max()
-			.ok_or("no solution")?;
+		let result = plane.occupied_seat_ids().max().ok_or("no solution")?;
 
 		println!("5a: {result}");
 
@@ -26,20 +19,12 @@ pub(super) fn run() -> Result<(), super::Error> {
 	}
 
 	{
-		let result =
-			plane.iter()
-			.enumerate()
-			.find_map(|(row_num, row)|
-				if row.iter().copied().any(std::convert::identity) {
-					row.iter()
-						.copied()
-						.position(std::ops::Not::not)
-						.map(|seat_num| seat_id(row_num, seat_num))
-				}
-				else {
-					None
-				})
-			.ok_or("no solution")?;
+		let mut gaps = plane.gaps();
+		let result = gaps.next().ok_or("no solution")?;
+		if gaps.next().is_some() {
+			return Err("more than one solution".into());
+		}
+		assert!(plane.missing_ranges().contains(&(result..=result)));
 
 		println!("5b: {result}");
 
@@ -49,22 +34,169 @@ pub(super) fn run() -> Result<(), super::Error> {
 	Ok(())
 }
 
-fn find_seat(pass: &str) -> Result<(usize, usize), super::Error> {
-	let seat_id: Result<usize, super::Error> =
-		pass.chars().try_fold(0, |row_num, c| match c {
-			'F' | 'L' => Ok(row_num * 2),
-			'B' | 'R' => Ok(row_num * 2 + 1),
-			_ => Err(format!("malformed pass {pass:?}").into()),
-		});
-	let seat_id = seat_id?;
-
-	let row_num = seat_id / 8;
-	let seat_num = seat_id % 8;
-	Ok((row_num, seat_num))
+/// The layout of the plane and the boarding pass format.
+///
+/// A boarding pass is `row_bits` letters from `row_letters` that binary-partition the rows, followed by
+/// `col_bits` letters from `col_letters` that binary-partition the seats in the row. In each pair of letters,
+/// the first one selects the lower half and the second one selects the upper half.
+#[derive(Clone, Copy, Debug)]
+struct Geometry {
+	row_bits: u32,
+	col_bits: u32,
+	row_letters: (char, char),
+	col_letters: (char, char),
 }
 
-fn seat_id(row_num: usize, seat_num: usize) -> usize {
-	row_num * 8 + seat_num
+impl Geometry {
+	/// Fails if seat IDs would not fit in a `usize`, or if either pair of letters is the same letter twice.
+	fn new(row_bits: u32, col_bits: u32, row_letters: (char, char), col_letters: (char, char)) -> Result<Self, super::Error> {
+		if row_bits.checked_add(col_bits).map_or(true, |num_bits| num_bits >= usize::BITS) {
+			return Err(format!("{row_bits} row bits and {col_bits} column bits are too many for seat IDs").into());
+		}
+
+		for (low, high) in [row_letters, col_letters] {
+			if low == high {
+				return Err(format!("letter {low:?} cannot select both the lower and the upper half").into());
+			}
+		}
+
+		Ok(Geometry { row_bits, col_bits, row_letters, col_letters })
+	}
+
+	fn num_rows(&self) -> usize {
+		1 << self.row_bits
+	}
+
+	fn num_cols(&self) -> usize {
+		1 << self.col_bits
+	}
+
+	fn find_seat(&self, pass: &str) -> Result<(usize, usize), super::Error> {
+		fn decode(chars: &mut std::str::Chars<'_>, num_bits: u32, (low, high): (char, char), pass: &str) -> Result<usize, super::Error> {
+			chars.take(num_bits as usize).try_fold(0, |num, c| match c {
+				c if c == low => Ok(num * 2),
+				c if c == high => Ok(num * 2 + 1),
+				c => Err(format!("malformed pass {pass:?}: expected {low:?} or {high:?} but got {c:?}").into()),
+			})
+		}
+
+		let expected_len = (self.row_bits + self.col_bits) as usize;
+		let len = pass.chars().count();
+		if len != expected_len {
+			return Err(format!("malformed pass {pass:?}: expected {expected_len} letters but got {len}").into());
+		}
+
+		let mut chars = pass.chars();
+		let row_num = decode(&mut chars, self.row_bits, self.row_letters, pass)?;
+		let seat_num = decode(&mut chars, self.col_bits, self.col_letters, pass)?;
+		Ok((row_num, seat_num))
+	}
+
+	fn encode_seat(&self, row_num: usize, seat_num: usize) -> Result<String, super::Error> {
+		fn encode(result: &mut String, num: usize, num_bits: u32, (low, high): (char, char)) {
+			for bit in (0..num_bits).rev() {
+				result.push(if (num >> bit) & 1 == 0 { low } else { high });
+			}
+		}
+
+		if row_num >= self.num_rows() || seat_num >= self.num_cols() {
+			return Err(format!("seat ({row_num}, {seat_num}) is outside the {}x{} plane", self.num_rows(), self.num_cols()).into());
+		}
+
+		let mut result = String::with_capacity((self.row_bits + self.col_bits) as usize);
+		encode(&mut result, row_num, self.row_bits, self.row_letters);
+		encode(&mut result, seat_num, self.col_bits, self.col_letters);
+		Ok(result)
+	}
+
+	fn seat_id(&self, row_num: usize, seat_num: usize) -> usize {
+		(row_num << self.col_bits) | seat_num
+	}
+}
+
+#[derive(Debug)]
+struct Plane {
+	geometry: Geometry,
+
+	/// The number of boarding passes for each seat, indexed by seat ID.
+	occupancy: Vec<usize>,
+}
+
+impl Plane {
+	fn new(geometry: Geometry) -> Self {
+		Plane {
+			geometry,
+			occupancy: vec![0; geometry.num_rows() * geometry.num_cols()],
+		}
+	}
+
+	fn board(&mut self, pass: &str) -> Result<(usize, usize), super::Error> {
+		let (row_num, seat_num) = self.geometry.find_seat(pass)?;
+		self.occupancy[self.geometry.seat_id(row_num, seat_num)] += 1;
+		Ok((row_num, seat_num))
+	}
+
+	fn occupied_seat_ids(&self) -> impl Iterator<Item = usize> + '_ {
+		self.occupancy.iter().enumerate().filter_map(|(seat_id, &num_passes)| (num_passes > 0).then(|| seat_id))
+	}
+
+	/// Empty seats whose neighboring seat IDs on both sides are occupied.
+	fn gaps(&self) -> impl Iterator<Item = usize> + '_ {
+		self.occupancy.windows(3)
+			.enumerate()
+			.filter_map(|(i, window)| (window[0] > 0 && window[1] == 0 && window[2] > 0).then(|| i + 1))
+	}
+
+	/// Maximal ranges of consecutive empty seat IDs, including any at the front and back of the plane.
+	fn missing_ranges(&self) -> Vec<std::ops::RangeInclusive<usize>> {
+		let mut result = vec![];
+
+		let mut start = None;
+		for (seat_id, &num_passes) in self.occupancy.iter().enumerate() {
+			match (start, num_passes) {
+				(None, 0) => start = Some(seat_id),
+				(Some(start_), num_passes) if num_passes > 0 => {
+					result.push(start_..=(seat_id - 1));
+					start = None;
+				},
+				_ => (),
+			}
+		}
+		if let Some(start) = start {
+			result.push(start..=(self.occupancy.len() - 1));
+		}
+
+		result
+	}
+
+	/// Seats with more than one boarding pass, and the number of boarding passes for them.
+	fn duplicates(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+		self.occupancy.iter().enumerate().filter_map(|(seat_id, &num_passes)| (num_passes > 1).then(|| (seat_id, num_passes)))
+	}
+
+	/// The number of occupied seats in each row.
+	fn row_occupancy(&self) -> impl Iterator<Item = usize> + '_ {
+		self.occupancy.chunks(self.geometry.num_cols())
+			.map(|row| row.iter().filter(|&&num_passes| num_passes > 0).count())
+	}
+
+	/// Renders each row on its own line, with `.` for an empty seat, `#` for an occupied seat
+	/// and `2`-`9` (or `*` for more) for a seat with that many boarding passes.
+	fn render(&self) -> String {
+		let mut result = String::with_capacity(self.occupancy.len() + self.geometry.num_rows());
+		for row in self.occupancy.chunks(self.geometry.num_cols()) {
+			for &num_passes in row {
+				result.push(match num_passes {
+					0 => '.',
+					1 => '#',
+					2..=9 => char::from(b'0' + u8::try_from(num_passes).expect("2..=9 fits in u8")),
+					_ => '*',
+				});
+			}
+			result.push('\n');
+		}
+		result
+	}
 }
 
 #[cfg(test)]
@@ -72,8 +204,10 @@ mod tests {
 	#[test]
 	fn find_seat_and_id() {
 		fn find_seat_and_id(pass: &str) -> (usize, usize, usize) {
-			let (row_num, seat_num) = super::find_seat(pass).unwrap();
-			let seat_id = super::seat_id(row_num, seat_num);
+			let geometry = super::Geometry::new(7, 3, ('F', 'B'), ('L', 'R')).unwrap();
+			let (row_num, seat_num) = geometry.find_seat(pass).unwrap();
+			let seat_id = geometry.seat_id(row_num, seat_num);
+			assert_eq!(geometry.encode_seat(row_num, seat_num).unwrap(), pass);
 			(row_num, seat_num, seat_id)
 		}
 
@@ -82,4 +216,48 @@ mod tests {
 		assert_eq!(find_seat_and_id("FFFBBBFRRR"), (14, 7, 119));
 		assert_eq!(find_seat_and_id("BBFFBBFRLL"), (102, 4, 820));
 	}
+
+	#[test]
+	fn custom_geometry() {
+		let geometry = super::Geometry::new(2, 4, ('0', '1'), ('a', 'b')).unwrap();
+
+		assert_eq!(geometry.find_seat("10abba").unwrap(), (2, 6));
+		assert_eq!(geometry.seat_id(2, 6), 38);
+		assert_eq!(geometry.encode_seat(2, 6).unwrap(), "10abba");
+
+		assert!(geometry.find_seat("10abb").is_err());
+		assert!(geometry.find_seat("10abbaa").is_err());
+		assert!(geometry.find_seat("1aabba").is_err());
+		assert!(geometry.encode_seat(4, 0).is_err());
+		assert!(geometry.encode_seat(0, 16).is_err());
+
+		assert!(super::Geometry::new(0, 0, ('F', 'B'), ('L', 'R')).is_ok());
+		assert!(super::Geometry::new(32, 31, ('F', 'B'), ('L', 'R')).is_ok());
+		assert!(super::Geometry::new(32, 32, ('F', 'B'), ('L', 'R')).is_err());
+		assert!(super::Geometry::new(64, 0, ('F', 'B'), ('L', 'R')).is_err());
+		assert!(super::Geometry::new(u32::MAX, 1, ('F', 'B'), ('L', 'R')).is_err());
+		assert!(super::Geometry::new(7, 3, ('F', 'F'), ('L', 'R')).is_err());
+		assert!(super::Geometry::new(7, 3, ('F', 'B'), ('R', 'R')).is_err());
+	}
+
+	#[test]
+	fn plane() {
+		let mut plane = super::Plane::new(super::Geometry::new(2, 2, ('F', 'B'), ('L', 'R')).unwrap());
+
+		for pass in ["FBLL", "FBLR", "FBRR", "BFLL", "BFLR", "BFRL", "BFRL", "BBLR"] {
+			plane.board(pass).unwrap();
+		}
+
+		assert_eq!(plane.occupied_seat_ids().collect::<Vec<_>>(), [4, 5, 7, 8, 9, 10, 13]);
+		assert_eq!(plane.gaps().collect::<Vec<_>>(), [6]);
+		assert_eq!(plane.missing_ranges(), [0..=3, 6..=6, 11..=12, 14..=15]);
+		assert_eq!(plane.duplicates().collect::<Vec<_>>(), [(10, 2)]);
+		assert_eq!(plane.row_occupancy().collect::<Vec<_>>(), [0, 3, 3, 1]);
+		assert_eq!(plane.render(), "\
+....
+##.#
+##2.
+.#..
+");
+	}
 }
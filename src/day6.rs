@@ -2,46 +2,62 @@ pub(super) fn run() -> Result<(), super::Error> {
 	{
 		let groups = part1(super::read_input_lines::<String>("day6")?)?;
 
-		let result: usize = groups.iter().map(std::collections::BTreeSet::len).sum();
+		let result: usize = groups.iter().map(Answers::len).sum();
 
 		println!("6a: {result}");
 
+		let tallies = tally(super::read_input_lines::<String>("day6")?)?;
+		let query: Query = "any".parse()?;
+		assert!(tallies.iter().map(|tally| query.evaluate(tally)).eq(groups.iter().map(Answers::to_set)));
+
 		assert_eq!(result, 6310);
 	}
 
 	{
 		let groups = part2(super::read_input_lines::<String>("day6")?)?;
 
-		let result: usize = groups.iter().map(std::collections::BTreeSet::len).sum();
+		let result: usize = groups.iter().map(Answers::len).sum();
 
 		println!("6b: {result}");
 
+		let tallies = tally(super::read_input_lines::<String>("day6")?)?;
+		let query: Query = "all".parse()?;
+		assert_eq!(query.per_question(&tallies).values().sum::<usize>(), result);
+
 		assert_eq!(result, 3193);
 	}
 
 	Ok(())
 }
 
-fn part1(input: impl Iterator<Item = Result<impl AsRef<str>, super::Error>>) -> Result<Vec<std::collections::BTreeSet<char>>, super::Error> {
-	parse_answers(input, |group, questions| questions.for_each(|question| {
-		group.insert(question);
-	}))
+fn part1(input: impl Iterator<Item = Result<impl AsRef<str>, super::Error>>) -> Result<Vec<Answers>, super::Error> {
+	parse_answers(input, Answers::new, Answers::union_with)
 }
 
-fn part2(input: impl Iterator<Item = Result<impl AsRef<str>, super::Error>>) -> Result<Vec<std::collections::BTreeSet<char>>, super::Error> {
-	parse_answers(input, |group, questions| {
-		let original_group = std::mem::take(group);
-		*group = original_group.intersection(&questions.collect()).copied().collect();
-	})
+fn part2(input: impl Iterator<Item = Result<impl AsRef<str>, super::Error>>) -> Result<Vec<Answers>, super::Error> {
+	parse_answers(input, Answers::new, Answers::intersect_with)
 }
 
-fn parse_answers(
+fn tally(input: impl Iterator<Item = Result<impl AsRef<str>, super::Error>>) -> Result<Vec<Tally>, super::Error> {
+	parse_answers(
+		input,
+		|questions| {
+			let mut tally: Tally = Default::default();
+			tally.add(questions);
+			tally
+		},
+		Tally::add,
+	)
+}
+
+fn parse_answers<G>(
 	input: impl Iterator<Item = Result<impl AsRef<str>, super::Error>>,
-	mut merge: impl FnMut(&mut std::collections::BTreeSet<char>, std::str::Chars<'_>),
-) -> Result<Vec<std::collections::BTreeSet<char>>, super::Error> {
+	mut new: impl FnMut(std::str::Chars<'_>) -> G,
+	mut merge: impl FnMut(&mut G, std::str::Chars<'_>),
+) -> Result<Vec<G>, super::Error> {
 	let mut groups = vec![];
 
-	let mut group: Option<std::collections::BTreeSet<_>> = None;
+	let mut group: Option<G> = None;
 	for line in input {
 		let line = line?;
 		let line = line.as_ref();
@@ -54,7 +70,7 @@ fn parse_answers(
 			merge(group, line.chars());
 		}
 		else {
-			group = Some(line.chars().collect());
+			group = Some(new(line.chars()));
 		}
 	}
 	if let Some(group) = group {
@@ -64,6 +80,267 @@ fn parse_answers(
 	Ok(groups)
 }
 
+/// The questions answered by a group.
+///
+/// Groups whose answers are all in `a`-`z` use a bitmask. Other groups fall back to a `BTreeSet`.
+#[derive(Debug)]
+enum Answers {
+	Small(SmallSet<Lowercase>), // Faster than `Large` for both parts; see `tests::bench_answers`
+	Large(std::collections::BTreeSet<char>),
+}
+
+impl Answers {
+	fn new(questions: std::str::Chars<'_>) -> Self {
+		SmallSet::from_questions(questions.clone()).map_or_else(|| Answers::Large(questions.collect()), Answers::Small)
+	}
+
+	fn union_with(&mut self, questions: std::str::Chars<'_>) {
+		if let (Answers::Small(group), Some(questions)) = (&mut *self, SmallSet::from_questions(questions.clone())) {
+			*group = group.union(questions);
+		}
+		else {
+			self.make_large().extend(questions);
+		}
+	}
+
+	fn intersect_with(&mut self, questions: std::str::Chars<'_>) {
+		if let (Answers::Small(group), Some(questions)) = (&mut *self, SmallSet::from_questions(questions.clone())) {
+			*group = group.intersection(questions);
+		}
+		else {
+			let questions: std::collections::BTreeSet<_> = questions.collect();
+			self.make_large().retain(|question| questions.contains(question));
+		}
+	}
+
+	fn len(&self) -> usize {
+		match self {
+			Answers::Small(group) => group.len(),
+			Answers::Large(group) => group.len(),
+		}
+	}
+
+	fn to_set(&self) -> std::collections::BTreeSet<char> {
+		match self {
+			Answers::Small(group) => group.iter().collect(),
+			Answers::Large(group) => group.clone(),
+		}
+	}
+
+	fn make_large(&mut self) -> &mut std::collections::BTreeSet<char> {
+		if let Answers::Small(group) = self {
+			*self = Answers::Large(group.iter().collect());
+		}
+
+		if let Answers::Large(group) = self {
+			group
+		}
+		else {
+			unreachable!()
+		}
+	}
+}
+
+/// A set of questions from an [`Alphabet`], stored as a bitmask.
+#[derive(Debug, Eq, PartialEq)]
+struct SmallSet<A> {
+	bits: u64,
+	_alphabet: std::marker::PhantomData<fn() -> A>,
+}
+
+// Not derived because the derives would require `A: Clone` / `A: Copy`
+
+impl<A> Clone for SmallSet<A> {
+	fn clone(&self) -> Self {
+		*self
+	}
+}
+
+impl<A> Copy for SmallSet<A> {}
+
+trait Alphabet {
+	/// Returns the position of the question in the alphabet, which must be less than 64, or `None` if the question is not in the alphabet.
+	fn position(question: char) -> Option<u32>;
+
+	fn question(position: u32) -> char;
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+struct Lowercase;
+
+impl Alphabet for Lowercase {
+	fn position(question: char) -> Option<u32> {
+		question.is_ascii_lowercase().then(|| u32::from(question) - u32::from('a'))
+	}
+
+	fn question(position: u32) -> char {
+		char::from(b'a' + u8::try_from(position).expect("position is less than 26"))
+	}
+}
+
+impl<A> SmallSet<A> where A: Alphabet {
+	/// Returns `None` if any of the questions is not in the alphabet.
+	fn from_questions(questions: impl Iterator<Item = char>) -> Option<Self> {
+		let mut bits = 0;
+		for question in questions {
+			bits |= 1 << A::position(question)?;
+		}
+		Some(SmallSet { bits, _alphabet: Default::default() })
+	}
+
+	fn union(self, other: Self) -> Self {
+		SmallSet { bits: self.bits | other.bits, _alphabet: Default::default() }
+	}
+
+	fn intersection(self, other: Self) -> Self {
+		SmallSet { bits: self.bits & other.bits, _alphabet: Default::default() }
+	}
+
+	fn len(self) -> usize {
+		self.bits.count_ones() as usize
+	}
+
+	fn iter(self) -> impl Iterator<Item = char> {
+		(0..64).filter(move |position| (self.bits >> position) & 1 == 1).map(A::question)
+	}
+}
+
+/// The number of people in a group, and the number of them that answered each question.
+#[derive(Debug, Default)]
+struct Tally {
+	num_people: usize,
+	counts: std::collections::BTreeMap<char, usize>,
+}
+
+impl Tally {
+	fn add(&mut self, questions: std::str::Chars<'_>) {
+		self.num_people += 1;
+
+		let questions: std::collections::BTreeSet<_> = questions.collect();
+		for question in questions {
+			*self.counts.entry(question).or_default() += 1;
+		}
+	}
+}
+
+/// A predicate over the number of people in a group that answered a question.
+///
+/// Grammar, where `not` binds tighter than `and`, which binds tighter than `or`:
+///
+/// ```text
+/// query := term ("or" term)*
+/// term := factor ("and" factor)*
+/// factor := "not" factor | "(" query ")" | "any" | "all" | "xor" | "exactly" N | "at" "least" N
+/// ```
+///
+/// `xor` matches questions answered by an odd number of people. `not` only matches questions that were answered by at least one person,
+/// so every query only ever matches questions that were answered by at least one person.
+#[derive(Debug, Eq, PartialEq)]
+enum Query {
+	Any,
+	All,
+	Xor,
+	Exactly(usize),
+	AtLeast(usize),
+	Not(Box<Query>),
+	And(Box<Query>, Box<Query>),
+	Or(Box<Query>, Box<Query>),
+}
+
+impl std::str::FromStr for Query {
+	type Err = super::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		fn parse_query<'a>(tokens: &mut std::iter::Peekable<impl Iterator<Item = &'a str>>) -> Result<Query, String> {
+			let mut query = parse_term(tokens)?;
+			while tokens.next_if_eq(&"or").is_some() {
+				query = Query::Or(Box::new(query), Box::new(parse_term(tokens)?));
+			}
+			Ok(query)
+		}
+
+		fn parse_term<'a>(tokens: &mut std::iter::Peekable<impl Iterator<Item = &'a str>>) -> Result<Query, String> {
+			let mut query = parse_factor(tokens)?;
+			while tokens.next_if_eq(&"and").is_some() {
+				query = Query::And(Box::new(query), Box::new(parse_factor(tokens)?));
+			}
+			Ok(query)
+		}
+
+		fn parse_factor<'a>(tokens: &mut std::iter::Peekable<impl Iterator<Item = &'a str>>) -> Result<Query, String> {
+			fn parse_num<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Result<usize, String> {
+				let num = tokens.next().ok_or_else(|| "expected number but got end of query".to_owned())?;
+				num.parse().map_err(|err| format!("invalid number {num:?}: {err}"))
+			}
+
+			match tokens.next() {
+				Some("not") => Ok(Query::Not(Box::new(parse_factor(tokens)?))),
+				Some("(") => {
+					let query = parse_query(tokens)?;
+					match tokens.next() {
+						Some(")") => Ok(query),
+						Some(token) => Err(format!("expected \")\" but got {token:?}")),
+						None => Err("expected \")\" but got end of query".to_owned()),
+					}
+				},
+				Some("any") => Ok(Query::Any),
+				Some("all") => Ok(Query::All),
+				Some("xor") => Ok(Query::Xor),
+				Some("exactly") => Ok(Query::Exactly(parse_num(tokens)?)),
+				Some("at") => match tokens.next() {
+					Some("least") => Ok(Query::AtLeast(parse_num(tokens)?)),
+					Some(token) => Err(format!("expected \"least\" but got {token:?}")),
+					None => Err("expected \"least\" but got end of query".to_owned()),
+				},
+				Some(token) => Err(format!("unexpected {token:?}")),
+				None => Err("unexpected end of query".to_owned()),
+			}
+		}
+
+		let spaced = s.replace('(', " ( ").replace(')', " ) ");
+		let mut tokens = spaced.split_whitespace().peekable();
+		let query = parse_query(&mut tokens).map_err(|err| format!("invalid query {s:?}: {err}"))?;
+		if let Some(token) = tokens.next() {
+			return Err(format!("invalid query {s:?}: unexpected {token:?}").into());
+		}
+		Ok(query)
+	}
+}
+
+impl Query {
+	fn matches(&self, count: usize, num_people: usize) -> bool {
+		match self {
+			Query::Any => count > 0,
+			Query::All => count == num_people,
+			Query::Xor => count % 2 == 1,
+			Query::Exactly(n) => count == *n,
+			Query::AtLeast(n) => count >= *n,
+			Query::Not(query) => !query.matches(count, num_people),
+			Query::And(left, right) => left.matches(count, num_people) && right.matches(count, num_people),
+			Query::Or(left, right) => left.matches(count, num_people) || right.matches(count, num_people),
+		}
+	}
+
+	/// The questions of the group that match this query.
+	fn evaluate(&self, tally: &Tally) -> std::collections::BTreeSet<char> {
+		tally.counts.iter()
+			.filter(|&(_, &count)| self.matches(count, tally.num_people))
+			.map(|(&question, _)| question)
+			.collect()
+	}
+
+	/// The number of groups in which each question matches this query.
+	fn per_question(&self, tallies: &[Tally]) -> std::collections::BTreeMap<char, usize> {
+		let mut result: std::collections::BTreeMap<_, _> = Default::default();
+		for tally in tallies {
+			for question in self.evaluate(tally) {
+				*result.entry(question).or_default() += 1;
+			}
+		}
+		result
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	const INPUT: &str = "\
@@ -86,7 +363,7 @@ b
 
 	#[test]
 	fn part1() {
-		let groups = super::part1(INPUT.split('\n').map(Ok)).unwrap();
+		let groups: Vec<_> = super::part1(INPUT.split('\n').map(Ok)).unwrap().iter().map(super::Answers::to_set).collect();
 		assert_eq!(&groups, &[
 			['a', 'b', 'c'].iter().copied().collect(),
 			['a', 'b', 'c'].iter().copied().collect(),
@@ -101,7 +378,7 @@ b
 
 	#[test]
 	fn part2() {
-		let groups = super::part2(INPUT.split('\n').map(Ok)).unwrap();
+		let groups: Vec<_> = super::part2(INPUT.split('\n').map(Ok)).unwrap().iter().map(super::Answers::to_set).collect();
 		assert_eq!(&groups, &[
 			['a', 'b', 'c'].iter().copied().collect(),
 			Default::default(),
@@ -113,4 +390,105 @@ b
 		let result: usize = groups.iter().map(std::collections::BTreeSet::len).sum();
 		assert_eq!(result, 6);
 	}
+
+	#[test]
+	fn query() {
+		let tallies = super::tally(INPUT.split('\n').map(Ok)).unwrap();
+		assert_eq!(tallies[2].num_people, 2);
+		assert_eq!(tallies[2].counts, [('a', 2), ('b', 1), ('c', 1)].iter().copied().collect());
+
+		let evaluate = |query: &str| -> Vec<String> {
+			let query: super::Query = query.parse().unwrap();
+			tallies.iter().map(|tally| query.evaluate(tally).into_iter().collect()).collect()
+		};
+
+		assert_eq!(evaluate("any"), ["abc", "abc", "abc", "a", "b"]);
+		assert_eq!(evaluate("all"), ["abc", "", "a", "a", "b"]);
+		assert_eq!(evaluate("exactly 1"), ["abc", "abc", "bc", "", "b"]);
+		assert_eq!(evaluate("at least 2"), ["", "", "a", "a", ""]);
+		assert_eq!(evaluate("xor"), ["abc", "abc", "bc", "", "b"]);
+		assert_eq!(evaluate("not all"), ["", "abc", "bc", "", ""]);
+		assert_eq!(evaluate("(exactly 1 or exactly 4) and not all"), ["", "abc", "bc", "", ""]);
+		assert_eq!(evaluate("all and not exactly 1 or exactly 4"), ["", "", "a", "a", ""]);
+
+		let query: super::Query = "all".parse().unwrap();
+		assert_eq!(query.per_question(&tallies), [('a', 3), ('b', 2), ('c', 1)].iter().copied().collect());
+
+		assert!("".parse::<super::Query>().is_err());
+		assert!("any any".parse::<super::Query>().is_err());
+		assert!("(any".parse::<super::Query>().is_err());
+		assert!("at most 2".parse::<super::Query>().is_err());
+		assert!("exactly two".parse::<super::Query>().is_err());
+	}
+
+	#[test]
+	fn answers_fallback() {
+		const INPUT: &str = "\
+abc
+bcD
+
+ab
+a1
+";
+
+		let groups = super::part1(INPUT.split('\n').map(Ok)).unwrap();
+		assert!(matches!(groups[0], super::Answers::Large(_)));
+		assert_eq!(groups[0].to_set(), ['D', 'a', 'b', 'c'].iter().copied().collect());
+		assert_eq!(groups[1].to_set(), ['1', 'a', 'b'].iter().copied().collect());
+
+		let groups = super::part2(INPUT.split('\n').map(Ok)).unwrap();
+		assert_eq!(groups[0].to_set(), ['b', 'c'].iter().copied().collect());
+		assert_eq!(groups[1].to_set(), std::iter::once('a').collect());
+
+		let groups = super::part2(INPUT.split('\n').take(1).map(Ok)).unwrap();
+		assert!(matches!(groups[0], super::Answers::Small(_)));
+		assert_eq!(groups[0].len(), 3);
+	}
+
+	#[test]
+	#[ignore = "benchmark"]
+	fn bench_answers() {
+		// The `BTreeSet`-only implementations from before `Answers` was introduced.
+		fn btreeset_part1(input: impl Iterator<Item = Result<impl AsRef<str>, super::super::Error>>) -> Vec<std::collections::BTreeSet<char>> {
+			super::parse_answers(input, |questions| questions.collect(), |group: &mut std::collections::BTreeSet<char>, questions| questions.for_each(|question| {
+				group.insert(question);
+			})).unwrap()
+		}
+
+		fn btreeset_part2(input: impl Iterator<Item = Result<impl AsRef<str>, super::super::Error>>) -> Vec<std::collections::BTreeSet<char>> {
+			super::parse_answers(input, |questions| questions.collect(), |group: &mut std::collections::BTreeSet<char>, questions| {
+				let original_group = std::mem::take(group);
+				*group = original_group.intersection(&questions.collect()).copied().collect();
+			}).unwrap()
+		}
+
+		let mut next = super::super::random_numbers();
+
+		let mut input = String::new();
+		for _ in 0..100_000 {
+			for _ in 0..=(next() % 5) {
+				for position in 0..26_u8 {
+					if next() % 2 == 0 {
+						input.push(char::from(b'a' + position));
+					}
+				}
+				input.push_str("z\n");
+			}
+			input.push('\n');
+		}
+		input.pop();
+
+		let start = std::time::Instant::now();
+		let btreeset_groups = (btreeset_part1(input.split('\n').map(Ok)), btreeset_part2(input.split('\n').map(Ok)));
+		let btreeset_elapsed = start.elapsed();
+
+		let start = std::time::Instant::now();
+		let groups = (super::part1(input.split('\n').map(Ok)).unwrap(), super::part2(input.split('\n').map(Ok)).unwrap());
+		let bitmask_elapsed = start.elapsed();
+
+		assert!(groups.0.iter().map(super::Answers::to_set).eq(btreeset_groups.0));
+		assert!(groups.1.iter().map(super::Answers::to_set).eq(btreeset_groups.1));
+
+		println!("BTreeSet: {btreeset_elapsed:?}, bitmask: {bitmask_elapsed:?}");
+	}
 }
@@ -1,8 +1,8 @@
 pub(super) fn run() -> Result<(), super::Error> {
-	let (graph, shiny_gold_node_index) = parse(super::read_input_lines::<String>("day7")?)?;
+	let bags = parse(super::read_input_lines::<String>("day7")?)?;
 
 	{
-		let result = part1(&graph, shiny_gold_node_index);
+		let result = bags.containers("shiny gold")?.len();
 
 		println!("7a: {result}");
 
@@ -10,25 +10,39 @@ pub(super) fn run() -> Result<(), super::Error> {
 	}
 
 	{
-		let result = part2(&graph, shiny_gold_node_index);
+		let result = bags.num_contained("shiny gold")?;
 
 		println!("7b: {result}");
 
 		assert_eq!(result, 7872);
+
+		let mut num_contained_via_paths = 0_usize;
+		for kind in bags.graph.node_weights() {
+			for (_, num) in bags.paths("shiny gold", kind)? {
+				num_contained_via_paths = num_contained_via_paths.checked_add(num).ok_or("number of bags inside shiny gold bags overflows")?;
+			}
+		}
+		assert_eq!(num_contained_via_paths, result);
+
+		assert_eq!(
+			bags.to_dot(Some("shiny gold"))?.matches(" -> ").count(),
+			bags.to_json(Some("shiny gold"))?.matches(r#""from":"#).count(),
+		);
+		assert_eq!(bags.to_dot(None)?.matches(" -> ").count(), bags.graph.edge_count());
 	}
 
 	Ok(())
 }
 
-fn parse(input: impl Iterator<Item = Result<impl AsRef<str>, super::Error>>) ->
-	Result<
-		(
-			petgraph::Graph<(), usize>,
-			petgraph::graph::NodeIndex<petgraph::graph::DefaultIx>,
-		),
-		super::Error,
-	>
-{
+/// The bag rules, as a graph with an edge from each kind of bag to each kind of bag it directly contains.
+/// Nodes are weighted by the kind of bag, and edges by the number of bags directly contained.
+#[derive(Debug)]
+struct Bags {
+	graph: petgraph::Graph<String, usize>,
+	nodes: std::collections::BTreeMap<String, petgraph::graph::NodeIndex<petgraph::graph::DefaultIx>>,
+}
+
+fn parse(input: impl Iterator<Item = Result<impl AsRef<str>, super::Error>>) -> Result<Bags, super::Error> {
 	static LINE_REGEX: once_cell::sync::Lazy<regex::Regex> =
 		once_cell::sync::Lazy::new(||
 			regex::Regex::new(r"^(?P<kind>\S+ \S+) bag(?:s?) contain (?P<contents>.+)\.$")
@@ -43,6 +57,16 @@ fn parse(input: impl Iterator<Item = Result<impl AsRef<str>, super::Error>>) ->
 
 	let mut nodes: std::collections::BTreeMap<_, _> = Default::default();
 
+	let mut get_or_add_node = |graph: &mut petgraph::Graph<String, usize>, kind: &str|
+		if let Some(&node_index) = nodes.get(kind) {
+			node_index
+		}
+		else {
+			let node_index = graph.add_node(kind.to_owned());
+			nodes.insert(kind.to_owned(), node_index);
+			node_index
+		};
+
 	for line in input {
 		let line = line?;
 		let line = line.as_ref();
@@ -52,15 +76,7 @@ fn parse(input: impl Iterator<Item = Result<impl AsRef<str>, super::Error>>) ->
 		let kind = &captures["kind"];
 		let contents = &captures["contents"];
 
-		let node_index =
-			if let Some(&node_index) = nodes.get(kind) {
-				node_index
-			}
-			else {
-				let node_index = graph.add_node(());
-				nodes.insert(kind.to_owned(), node_index);
-				node_index
-			};
+		let node_index = get_or_add_node(&mut graph, kind);
 
 		if contents == "no other bags" {
 			continue;
@@ -74,15 +90,7 @@ fn parse(input: impl Iterator<Item = Result<impl AsRef<str>, super::Error>>) ->
 			let content_num: usize = captures["num"].parse()?;
 			let content_kind = &captures["kind"];
 
-			let content_node_index =
-				if let Some(&node_index) = nodes.get(content_kind) {
-					node_index
-				}
-				else {
-					let node_index = graph.add_node(());
-					nodes.insert(content_kind.to_owned(), node_index);
-					node_index
-				};
+			let content_node_index = get_or_add_node(&mut graph, content_kind);
 
 			graph.update_edge(node_index, content_node_index, content_num);
 		}
@@ -90,35 +98,196 @@ fn parse(input: impl Iterator<Item = Result<impl AsRef<str>, super::Error>>) ->
 
 	graph.shrink_to_fit();
 
-	let &shiny_gold_node_index = nodes.get("shiny gold").ok_or("could not find rule for shiny gold bags")?;
-
-	Ok((graph, shiny_gold_node_index))
+	Ok(Bags { graph, nodes })
 }
 
-fn part1(graph: &petgraph::Graph<(), usize>, shiny_gold_node_index: petgraph::graph::NodeIndex<petgraph::graph::DefaultIx>) -> usize {
-	let graph = petgraph::visit::Reversed(graph);
-	let walker = petgraph::visit::Bfs::new(graph, shiny_gold_node_index);
-	let walker = petgraph::visit::Walker::iter(walker, graph);
-	walker.count() - 1 // Don't count the original shiny gold bag.
-}
+impl Bags {
+	fn node_index(&self, kind: &str) -> Result<petgraph::graph::NodeIndex<petgraph::graph::DefaultIx>, super::Error> {
+		Ok(*self.nodes.get(kind).ok_or_else(|| format!("could not find rule for {kind} bags"))?)
+	}
+
+	/// The kinds of bags that can eventually contain at least one bag of the given kind.
+	fn containers(&self, kind: &str) -> Result<std::collections::BTreeSet<&str>, super::Error> {
+		let node_index = self.node_index(kind)?;
+
+		let graph = petgraph::visit::Reversed(&self.graph);
+		let walker = petgraph::visit::Bfs::new(graph, node_index);
+		let walker = petgraph::visit::Walker::iter(walker, graph);
+		Ok(
+			walker
+			.filter(|&container_node_index| container_node_index != node_index) // Don't count the original bag.
+			.map(|container_node_index| &*self.graph[container_node_index])
+			.collect()
+		)
+	}
+
+	/// The total number of bags inside a bag of the given kind.
+	///
+	/// Fails if the bags reachable from the given kind contain a cycle, or if the total overflows.
+	fn num_contained(&self, kind: &str) -> Result<usize, super::Error> {
+		let node_index = self.node_index(kind)?;
+
+		// Number of bags inside each kind of bag, not including itself.
+		let mut num_contained = vec![0_usize; self.graph.node_count()];
+
+		for container_node_index in self.topological_order(node_index)? {
+			let mut num = 0_usize;
+
+			for edge in self.graph.edges(container_node_index) {
+				let &content_num = edge.weight();
+				let content_node_index = petgraph::visit::EdgeRef::target(&edge);
+				num =
+					num_contained[content_node_index.index()].checked_add(1)
+					.and_then(|content_total| content_total.checked_mul(content_num))
+					.and_then(|content_total| num.checked_add(content_total))
+					.ok_or_else(|| format!("number of bags inside {} bags overflows", self.graph[container_node_index]))?;
+			}
+
+			num_contained[container_node_index.index()] = num;
+		}
+
+		Ok(num_contained[node_index.index()])
+	}
+
+	/// The kinds of bags that can be reached from the given kind, such that every kind comes after all the kinds it contains.
+	///
+	/// Fails with the offending cycle if the reachable bags contain a cycle.
+	fn topological_order(
+		&self,
+		node_index: petgraph::graph::NodeIndex<petgraph::graph::DefaultIx>,
+	) -> Result<Vec<petgraph::graph::NodeIndex<petgraph::graph::DefaultIx>>, super::Error> {
+		#[derive(Clone, Copy, Eq, PartialEq)]
+		enum Mark {
+			Unvisited,
+			Visiting,
+			Visited,
+		}
+
+		let mut marks = vec![Mark::Unvisited; self.graph.node_count()];
+		let mut result = vec![];
+
+		marks[node_index.index()] = Mark::Visiting;
+		let mut stack = vec![(node_index, self.graph.neighbors(node_index))];
+
+		while let Some((node_index, neighbors)) = stack.last_mut() {
+			let node_index = *node_index;
+
+			if let Some(content_node_index) = neighbors.next() {
+				match marks[content_node_index.index()] {
+					Mark::Unvisited => {
+						marks[content_node_index.index()] = Mark::Visiting;
+						stack.push((content_node_index, self.graph.neighbors(content_node_index)));
+					},
+
+					Mark::Visiting => {
+						let cycle_start = stack.iter().position(|&(node_index, _)| node_index == content_node_index).expect("node being visited is on the stack");
+						let cycle: Vec<_> =
+							stack[cycle_start..].iter()
+							.map(|&(node_index, _)| &*self.graph[node_index])
+							.chain(std::iter::once(&*self.graph[content_node_index]))
+							.collect();
+						return Err(format!("bag rules contain a cycle: {}", cycle.join(" -> ")).into());
+					},
+
+					Mark::Visited => (),
+				}
+			}
+			else {
+				marks[node_index.index()] = Mark::Visited;
+				result.push(node_index);
+				stack.pop();
+			}
+		}
+
+		Ok(result)
+	}
+
+	/// The graph restricted to the kinds of bags that can be reached from the given kind, or the whole graph if no kind is given.
+	fn subgraph(&self, root: Option<&str>) -> Result<petgraph::Graph<&str, usize>, super::Error> {
+		let reachable: Option<std::collections::BTreeSet<_>> = match root {
+			Some(root) => {
+				let walker = petgraph::visit::Bfs::new(&self.graph, self.node_index(root)?);
+				Some(petgraph::visit::Walker::iter(walker, &self.graph).collect())
+			},
+			None => None,
+		};
+
+		Ok(self.graph.filter_map(
+			|node_index, kind| reachable.as_ref().map_or(true, |reachable| reachable.contains(&node_index)).then(|| &**kind),
+			|_, &num| Some(num),
+		))
+	}
+
+	/// Exports the graph in Graphviz DOT format, optionally restricted to the bags reachable from `root`.
+	fn to_dot(&self, root: Option<&str>) -> Result<String, super::Error> {
+		let graph = self.subgraph(root)?;
+		Ok(petgraph::dot::Dot::new(&graph).to_string())
+	}
 
-fn part2(graph: &petgraph::Graph<(), usize>, shiny_gold_node_index: petgraph::graph::NodeIndex<petgraph::graph::DefaultIx>) -> usize {
-	let mut result = 0;
+	/// Exports the graph as JSON, optionally restricted to the bags reachable from `root`.
+	///
+	/// The JSON is an object with a `nodes` array of kinds of bags, and an `edges` array of `{ "from", "to", "num" }` objects.
+	fn to_json(&self, root: Option<&str>) -> Result<String, super::Error> {
+		use std::fmt::Write;
 
-	let mut to_visit: std::collections::VecDeque<_> = std::iter::once((shiny_gold_node_index, 1)).collect();
+		let graph = self.subgraph(root)?;
 
-	while let Some((node_index, num)) = to_visit.pop_front() {
-		result += num;
+		let mut result = String::from(r#"{"nodes":["#);
 
-		for edge in graph.edges(node_index) {
-			let &content_num = edge.weight();
-			let content_node_index = petgraph::visit::EdgeRef::target(&edge);
-			to_visit.push_back((content_node_index, num * content_num));
+		for (i, kind) in graph.node_weights().enumerate() {
+			if i > 0 {
+				result.push(',');
+			}
+			super::push_json_string(&mut result, kind);
 		}
+
+		result.push_str(r#"],"edges":["#);
+
+		for (i, edge) in graph.raw_edges().iter().enumerate() {
+			if i > 0 {
+				result.push(',');
+			}
+			result.push_str(r#"{"from":"#);
+			super::push_json_string(&mut result, graph[edge.source()]);
+			result.push_str(r#","to":"#);
+			super::push_json_string(&mut result, graph[edge.target()]);
+			write!(result, r#","num":{}}}"#, edge.weight).expect("cannot fail to write to String");
+		}
+
+		result.push_str("]}");
+
+		Ok(result)
 	}
 
-	// Don't count the original shiny gold bag.
-	result - 1
+	/// Every path of containment from a bag of kind `from` to bags of kind `to`, along with the number of `to` bags
+	/// inside one `from` bag via that path.
+	///
+	/// The multiplicities of all paths sum to the total number of `to` bags inside one `from` bag.
+	///
+	/// Fails if the multiplicity of any path overflows.
+	fn paths(&self, from: &str, to: &str) -> Result<Vec<(Vec<&str>, usize)>, super::Error> {
+		let from = self.node_index(from)?;
+		let to = self.node_index(to)?;
+
+		if from == to {
+			return Ok(vec![]);
+		}
+
+		let paths = petgraph::algo::all_simple_paths::<Vec<_>, _>(&self.graph, from, to, 0, None);
+		paths
+		.map(|path| {
+			let num =
+				path.windows(2)
+				.try_fold(1_usize, |num, edge| {
+					let edge_index = self.graph.find_edge(edge[0], edge[1]).expect("path consists of edges of the graph");
+					num.checked_mul(self.graph[edge_index])
+				})
+				.ok_or_else(|| format!("number of {} bags inside {} bags overflows", self.graph[to], self.graph[from]))?;
+			let path = path.into_iter().map(|node_index| &*self.graph[node_index]).collect();
+			Ok((path, num))
+		})
+		.collect()
+	}
 }
 
 #[cfg(test)]
@@ -137,8 +306,13 @@ dotted black bags contain no other bags.\
 
 	#[test]
 	fn part1() {
-		let (graph, shiny_gold_node_index) = super::parse(INPUT.split('\n').map(Ok)).unwrap();
-		assert_eq!(super::part1(&graph, shiny_gold_node_index), 4);
+		let bags = super::parse(INPUT.split('\n').map(Ok)).unwrap();
+		assert_eq!(
+			bags.containers("shiny gold").unwrap(),
+			["bright white", "dark orange", "light red", "muted yellow"].iter().copied().collect(),
+		);
+		assert_eq!(bags.containers("light red").unwrap(), Default::default());
+		assert!(bags.containers("plaid purple").is_err());
 	}
 
 	#[test]
@@ -153,10 +327,108 @@ dark blue bags contain 2 dark violet bags.
 dark violet bags contain no other bags.\
 ";
 
-		let (graph, shiny_gold_node_index) = super::parse(INPUT.split('\n').map(Ok)).unwrap();
-		assert_eq!(super::part2(&graph, shiny_gold_node_index), 32);
+		let bags = super::parse(INPUT.split('\n').map(Ok)).unwrap();
+		assert_eq!(bags.num_contained("shiny gold").unwrap(), 32);
+		assert_eq!(bags.num_contained("dark olive").unwrap(), 7);
 
-		let (graph, shiny_gold_node_index) = super::parse(INPUT2.split('\n').map(Ok)).unwrap();
-		assert_eq!(super::part2(&graph, shiny_gold_node_index), 126);
+		let bags = super::parse(INPUT2.split('\n').map(Ok)).unwrap();
+		assert_eq!(bags.num_contained("shiny gold").unwrap(), 126);
+	}
+
+	#[test]
+	fn part2_cycle() {
+		const INPUT: &str = "\
+shiny gold bags contain 2 dark red bags.
+dark red bags contain 1 dark orange bag, 3 dark blue bags.
+dark orange bags contain 2 dark yellow bags.
+dark yellow bags contain 1 dark red bag.
+dark blue bags contain no other bags.\
+";
+
+		let bags = super::parse(INPUT.split('\n').map(Ok)).unwrap();
+		assert_eq!(
+			bags.num_contained("shiny gold").unwrap_err().0.to_string(),
+			"bag rules contain a cycle: dark red -> dark orange -> dark yellow -> dark red",
+		);
+		assert_eq!(bags.num_contained("dark blue").unwrap(), 0);
+
+		let bags = super::parse(std::iter::once(Ok("shiny gold bags contain 1 shiny gold bag."))).unwrap();
+		assert_eq!(
+			bags.num_contained("shiny gold").unwrap_err().0.to_string(),
+			"bag rules contain a cycle: shiny gold -> shiny gold",
+		);
+	}
+
+	#[test]
+	fn part2_deep() {
+		// Each level contains one each of two kinds of bags, both of which contain one each of the two kinds of the next level,
+		// so the number of paths doubles with each level.
+		let input: Vec<_> =
+			(0..40)
+			.flat_map(|level| {
+				let contents = format!("1 light c{} bag, 1 dark c{} bag.", level + 1, level + 1);
+				vec![
+					format!("light c{level} bags contain {contents}"),
+					format!("dark c{level} bags contain {contents}"),
+				]
+			})
+			.collect();
+
+		let bags = super::parse(input.iter().map(Ok)).unwrap();
+		assert_eq!(bags.num_contained("light c0").unwrap(), (1 << 41) - 2);
+
+		// Each level contains 999 of the next level.
+		let input: Vec<_> = (0..10).map(|level| format!("light c{level} bags contain 999 light c{} bags.", level + 1)).collect();
+
+		let bags = super::parse(input.iter().map(Ok)).unwrap();
+		assert_eq!(bags.num_contained("light c4").unwrap(), (1..=6).map(|i| 999_usize.pow(i)).sum());
+		assert!(bags.num_contained("light c0").is_err());
+
+		let kinds: Vec<_> = (4..=10).map(|level| format!("light c{level}")).collect();
+		assert_eq!(bags.paths("light c4", "light c10").unwrap(), [(kinds.iter().map(String::as_str).collect(), 999_usize.pow(6))]);
+		assert!(bags.paths("light c0", "light c10").is_err());
+	}
+
+	#[test]
+	fn export() {
+		let bags = super::parse(INPUT.split('\n').map(Ok)).unwrap();
+
+		assert_eq!(bags.to_dot(Some("dark olive")).unwrap(), "\
+digraph {
+    0 [ label = \"faded blue\" ]
+    1 [ label = \"dark olive\" ]
+    2 [ label = \"dotted black\" ]
+    1 -> 0 [ label = \"3\" ]
+    1 -> 2 [ label = \"4\" ]
+}
+");
+
+		assert_eq!(
+			bags.to_json(Some("dark olive")).unwrap(),
+			r#"{"nodes":["faded blue","dark olive","dotted black"],"edges":[{"from":"dark olive","to":"faded blue","num":3},{"from":"dark olive","to":"dotted black","num":4}]}"#,
+		);
+
+		let json = bags.to_json(None).unwrap();
+		assert_eq!(json.matches(r#""from":"#).count(), 13);
+		assert!(json.starts_with(r#"{"nodes":["light red","bright white","muted yellow","dark orange","#));
+
+		assert!(bags.to_dot(Some("plaid purple")).is_err());
+	}
+
+	#[test]
+	fn paths() {
+		let bags = super::parse(INPUT.split('\n').map(Ok)).unwrap();
+
+		let mut paths = bags.paths("light red", "faded blue").unwrap();
+		paths.sort();
+		assert_eq!(paths, [
+			(vec!["light red", "bright white", "shiny gold", "dark olive", "faded blue"], 3),
+			(vec!["light red", "bright white", "shiny gold", "vibrant plum", "faded blue"], 10),
+			(vec!["light red", "muted yellow", "faded blue"], 18),
+			(vec!["light red", "muted yellow", "shiny gold", "dark olive", "faded blue"], 12),
+			(vec!["light red", "muted yellow", "shiny gold", "vibrant plum", "faded blue"], 40),
+		]);
+
+		assert_eq!(bags.paths("faded blue", "light red").unwrap(), []);
 	}
 }
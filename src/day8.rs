@@ -1,5 +1,7 @@
 pub(super) fn run() -> Result<(), super::Error> {
-	let instructions = parse_program(super::read_input_lines("day8")?)?;
+	let opcodes = OpcodeTable::standard();
+
+	let instructions = parse_program(super::read_input_lines::<String>("day8")?, &opcodes)?;
 
 	{
 		let result = part1(&instructions)?;
@@ -7,24 +9,51 @@ pub(super) fn run() -> Result<(), super::Error> {
 		println!("8a: {result}");
 
 		assert_eq!(result, 1818);
+
+		// A program that loops forever must revisit some instruction after executing as many instructions as the program has.
+		let mut vm = Vm::new(&instructions).with_limit(instructions.len());
+		assert_eq!(vm.run()?, Status::LimitReached);
+
+		let mut debugger = Debugger::new(Vm::new(&instructions).with_limit(instructions.len()), &opcodes);
+		assert_eq!(debugger.command("continue")?, "stopped: instruction limit reached\n");
+		assert_eq!(debugger.command("trace")?.lines().count(), instructions.len());
+		assert_eq!((0..instructions.len()).map(|pc| debugger.execution_count(pc)).sum::<usize>(), instructions.len());
 	}
 
 	{
-		let result = part2(&instructions)?;
+		let result = part2(&instructions, &opcodes)?;
 
 		println!("8b: {result}");
 
 		assert_eq!(result, 631);
+
+		// The extended opcodes are a superset of the standard ones.
+		let instructions = parse_program(super::read_input_lines::<String>("day8")?, &OpcodeTable::extended())?;
+		assert_eq!(part2(&instructions, &opcodes)?, result);
+	}
+
+	{
+		let analysis = analyze(&instructions, &opcodes)?;
+		assert!(!analysis.terminates);
+		assert_eq!(analysis.infinite_loops.len(), 1);
+		assert!(analysis.out_of_range_jumps.is_empty());
+
+		// The static analysis finds the same repair as part 2, and only that one.
+		let (repaired_pc, _) = repair(&instructions, &opcodes)?;
+		let repairs: Vec<_> = analysis.repairs.iter().map(|&(pc, _)| pc).collect();
+		assert_eq!(repairs, [repaired_pc]);
+		assert!(!analysis.unreachable.contains(&repaired_pc));
 	}
 
 	Ok(())
 }
 
-fn parse_program(input: impl Iterator<Item = Result<Instruction, super::Error>>) -> Result<Vec<Instruction>, super::Error> {
+fn parse_program(input: impl Iterator<Item = Result<impl AsRef<str>, super::Error>>, opcodes: &OpcodeTable) -> Result<Vec<Instruction>, super::Error> {
 	let mut instructions = vec![];
 
-	for instruction in input {
-		let instruction = instruction?;
+	for line in input {
+		let line = line?;
+		let instruction = opcodes.parse(line.as_ref())?;
 		instructions.push(instruction);
 	}
 
@@ -34,85 +63,744 @@ fn parse_program(input: impl Iterator<Item = Result<Instruction, super::Error>>)
 fn part1(instructions: &[Instruction]) -> Result<i64, super::Error> {
 	match boot(instructions)? {
 		BootResult::InfiniteLoop(acc) => Ok(acc),
-		BootResult::Finished(_) => Err("expected program to infinite loop but it finished".into()),
+		BootResult::Finished => Err("expected program to infinite loop but it finished".into()),
 	}
 }
 
-fn part2(instructions: &[Instruction]) -> Result<i64, super::Error> {
-	for (i, _) in instructions.iter().enumerate().filter(|(_, instruction)| matches!(instruction, Instruction::Jmp(_))) {
-		let mut instructions = instructions.to_owned();
-		instructions[i] = Instruction::Nop(0);
-		if let BootResult::Finished(acc) = boot(&instructions)? {
-			return Ok(acc);
+fn part2(instructions: &[Instruction], opcodes: &OpcodeTable) -> Result<i64, super::Error> {
+	let (_, acc) = repair(instructions, opcodes)?;
+	Ok(acc)
+}
+
+/// Finds the instruction whose opcode can be flipped between `jmp` and `nop` to make the program finish,
+/// and returns its index and the value of `acc` when the repaired program finishes.
+///
+/// If more than one instruction could be flipped, the one that the original program reaches first is returned.
+/// Conditional jumps are not supported.
+fn repair(instructions: &[Instruction], opcodes: &OpcodeTable) -> Result<(usize, i64), super::Error> {
+	enum Successor {
+		Pc(i64),
+		Halt,
+
+		/// The instruction jumps to a pc that overflows, which fails the VM.
+		Overflow,
+	}
+
+	let num_instructions = instructions.len();
+
+	let successor = |pc: usize, instruction: &Instruction| -> Result<Successor, super::Error> {
+		let pc = i64::try_from(pc)?;
+		match instruction.successors(pc) {
+			(Some(successor), None) | (None, Some(successor)) => Ok(Successor::Pc(successor)),
+			(None, None) if instruction.opcode.flows == Flows::Halt => Ok(Successor::Halt),
+			(None, None) => Ok(Successor::Overflow),
+			(Some(_), Some(_)) => Err(format!("cannot repair program with conditional jump {instruction} at pc {pc}").into()),
+		}
+	};
+
+	// A pc terminates if executing from there eventually finishes or halts.
+	let terminates = {
+		let mut predecessors = vec![vec![]; num_instructions];
+		let mut to_visit = vec![];
+
+		for (pc, instruction) in instructions.iter().enumerate() {
+			match successor(pc, instruction)? {
+				Successor::Halt => to_visit.push(pc),
+				Successor::Pc(successor) => match usize::try_from(successor) {
+					Ok(successor) if successor < num_instructions => predecessors[successor].push(pc),
+					Ok(_) => to_visit.push(pc),
+					Err(_) => (),
+				},
+				Successor::Overflow => (),
+			}
+		}
+
+		let mut terminates = vec![false; num_instructions];
+		while let Some(pc) = to_visit.pop() {
+			if !std::mem::replace(&mut terminates[pc], true) {
+				to_visit.extend_from_slice(&predecessors[pc]);
+			}
 		}
+
+		move |pc: i64| usize::try_from(pc).map_or(false, |pc| terminates.get(pc).copied().unwrap_or(true))
+	};
+
+	// Any instruction that the original program does not reach cannot change its behavior when flipped, and any instruction that it does reach
+	// does not terminate since the original program does not. So the flipped instruction must be one that the original program reaches,
+	// and its new successor must terminate, without going through the flipped instruction.
+	let jmp = opcodes.get("jmp")?;
+	let nop = opcodes.get("nop")?;
+
+	if terminates(0) {
+		return Err("program already finishes without being repaired".into());
 	}
 
-	Err("no solution".into())
+	let mut visited = vec![false; num_instructions];
+	let mut pc = 0_i64;
+	let flipped = loop {
+		let pc_ = usize::try_from(pc).map_err(|err| format!("pc out of range: {err}"))?;
+		let instruction = instructions.get(pc_).expect("original program does not terminate");
+		if std::mem::replace(&mut visited[pc_], true) {
+			return Err("no solution".into());
+		}
+
+		let flipped_opcode = match instruction.opcode.name {
+			"jmp" => Some(nop),
+			"nop" => Some(jmp),
+			_ => None,
+		};
+		if let Some(flipped_opcode) = flipped_opcode {
+			let flipped = Instruction { opcode: flipped_opcode, arg: instruction.arg };
+			let flipped_terminates = match successor(pc_, &flipped)? {
+				Successor::Pc(successor) => terminates(successor),
+				Successor::Halt => true,
+				Successor::Overflow => false,
+			};
+			if flipped_terminates {
+				break (pc_, flipped);
+			}
+		}
+
+		pc = match successor(pc_, instruction)? {
+			Successor::Pc(successor) => successor,
+			Successor::Halt => unreachable!("original program does not terminate"),
+			Successor::Overflow => return Err(format!("pc out of range after {instruction} at pc {pc}").into()),
+		};
+	};
+
+	let mut instructions = instructions.to_owned();
+	instructions[flipped.0] = flipped.1;
+	let mut vm = Vm::new(&instructions);
+	match vm.run()? {
+		Status::Finished | Status::Halted => Ok((flipped.0, vm.registers.acc)),
+		Status::Running | Status::LimitReached => unreachable!(),
+	}
 }
 
-#[derive(Clone, Copy, Debug)]
-enum Instruction {
-	Jmp(i64),
-	Acc(i64),
-	Nop(i64),
-}
-
-impl std::str::FromStr for Instruction {
-	type Err = super::Error;
-
-	fn from_str(s: &str) -> Result<Self, Self::Err> {
-		let arg =
-			s.get(4..)
-			.ok_or_else(|| format!("invalid instruction {s:?}: no arg"))?
-			.parse()
-			.map_err(|err| format!("invalid instruction {s:?}: {err}"))?;
-
-		let instruction = match &s[..3] {
-			"jmp" => Instruction::Jmp(arg),
-			"acc" => Instruction::Acc(arg),
-			"nop" => Instruction::Nop(arg),
-			_ => return Err(format!("invalid instruction {s:?}: unrecognized opcode").into()),
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+struct Registers {
+	pc: i64,
+	acc: i64,
+}
+
+/// What the VM should do after executing an instruction.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Flow {
+	/// Continue with the next instruction.
+	Next,
+
+	/// Continue with the instruction at the given offset from the current one.
+	Jump(i64),
+
+	/// Stop executing.
+	Halt,
+}
+
+/// The [`Flow`]s that an opcode can return, for static analysis of programs.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Flows {
+	Next,
+	Jump,
+	NextOrJump,
+	Halt,
+}
+
+#[derive(Clone, Copy)]
+struct Opcode {
+	name: &'static str,
+	flows: Flows,
+	execute: fn(&mut Registers, i64) -> Result<Flow, super::Error>,
+}
+
+impl std::fmt::Debug for Opcode {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(self.name)
+	}
+}
+
+/// The opcodes that a program may use, by name.
+#[derive(Debug)]
+struct OpcodeTable {
+	opcodes: std::collections::BTreeMap<&'static str, Opcode>,
+}
+
+impl OpcodeTable {
+	/// `acc`, `jmp` and `nop`
+	fn standard() -> Self {
+		let mut result = OpcodeTable { opcodes: Default::default() };
+
+		result.insert("acc", Flows::Next, |registers, arg| {
+			registers.acc = registers.acc.checked_add(arg).ok_or("acc overflow")?;
+			Ok(Flow::Next)
+		});
+
+		result.insert("jmp", Flows::Jump, |_, arg| Ok(Flow::Jump(arg)));
+
+		result.insert("nop", Flows::Next, |_, _| Ok(Flow::Next));
+
+		result
+	}
+
+	/// The standard opcodes, and:
+	///
+	/// - `mul`: multiply `acc` by the argument
+	/// - `jz` / `jnz`: jump by the argument if `acc` is zero / non-zero, otherwise continue with the next instruction
+	/// - `hlt`: stop executing, ignoring the argument
+	fn extended() -> Self {
+		let mut result = Self::standard();
+
+		result.insert("mul", Flows::Next, |registers, arg| {
+			registers.acc = registers.acc.checked_mul(arg).ok_or("acc overflow")?;
+			Ok(Flow::Next)
+		});
+
+		result.insert("jz", Flows::NextOrJump, |registers, arg| Ok(if registers.acc == 0 { Flow::Jump(arg) } else { Flow::Next }));
+
+		result.insert("jnz", Flows::NextOrJump, |registers, arg| Ok(if registers.acc == 0 { Flow::Next } else { Flow::Jump(arg) }));
+
+		result.insert("hlt", Flows::Halt, |_, _| Ok(Flow::Halt));
+
+		result
+	}
+
+	fn insert(&mut self, name: &'static str, flows: Flows, execute: fn(&mut Registers, i64) -> Result<Flow, super::Error>) {
+		self.opcodes.insert(name, Opcode { name, flows, execute });
+	}
+
+	fn get(&self, name: &str) -> Result<Opcode, super::Error> {
+		Ok(*self.opcodes.get(name).ok_or_else(|| format!("unrecognized opcode {name:?}"))?)
+	}
+
+	/// Parses an instruction of the form `<opcode> <arg>`. The arg is optional and defaults to 0.
+	fn parse(&self, s: &str) -> Result<Instruction, super::Error> {
+		let mut parts = s.split_whitespace();
+
+		let opcode = parts.next().ok_or_else(|| format!("invalid instruction {s:?}: no opcode"))?;
+		let opcode = self.get(opcode).map_err(|err| format!("invalid instruction {s:?}: {}", err.0))?;
+
+		let arg = match parts.next() {
+			Some(arg) => arg.parse().map_err(|err| format!("invalid instruction {s:?}: {err}"))?,
+			None => 0,
 		};
-		Ok(instruction)
+
+		if parts.next().is_some() {
+			return Err(format!("invalid instruction {s:?}: too many args").into());
+		}
+
+		Ok(Instruction { opcode, arg })
 	}
 }
 
-fn boot(instructions: &[Instruction]) -> Result<BootResult, super::Error> {
-	let mut executed: std::collections::BTreeSet<_> = Default::default();
+#[derive(Clone, Copy, Debug)]
+struct Instruction {
+	opcode: Opcode,
+	arg: i64,
+}
+
+impl Instruction {
+	/// The pc that execution continues at after executing this instruction at `pc` if it jumps, and if it doesn't.
+	fn successors(&self, pc: i64) -> (Option<i64>, Option<i64>) {
+		let jump = || pc.checked_add(self.arg);
+		let next = || pc.checked_add(1);
+		match self.opcode.flows {
+			Flows::Next => (None, next()),
+			Flows::Jump => (jump(), None),
+			Flows::NextOrJump => (jump(), next()),
+			Flows::Halt => (None, None),
+		}
+	}
+}
 
-	let mut pc = 0;
-	let mut acc = 0;
+impl std::fmt::Display for Instruction {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} {:+}", self.opcode.name, self.arg)
+	}
+}
 
-	loop {
-		if !executed.insert(pc) {
-			return Ok(BootResult::InfiniteLoop(acc));
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Status {
+	Running,
+
+	/// The program tried to execute the instruction immediately after the last one, or any other instruction after that.
+	Finished,
+
+	/// The program executed an instruction whose opcode returned [`Flow::Halt`].
+	Halted,
+
+	/// The VM already executed as many instructions as its limit.
+	LimitReached,
+}
+
+#[derive(Debug)]
+struct Vm<'a> {
+	instructions: &'a [Instruction],
+	registers: Registers,
+	num_executed: usize,
+	limit: Option<usize>,
+	status: Status,
+}
+
+impl<'a> Vm<'a> {
+	fn new(instructions: &'a [Instruction]) -> Self {
+		Vm {
+			instructions,
+			registers: Default::default(),
+			num_executed: 0,
+			limit: None,
+			status: Status::Running,
+		}
+	}
+
+	fn with_limit(mut self, limit: usize) -> Self {
+		self.limit = Some(limit);
+		self
+	}
+
+	/// The instruction at the current pc, or `None` if the program has finished.
+	fn current(&self) -> Result<Option<&'a Instruction>, super::Error> {
+		let pc: usize = std::convert::TryInto::try_into(self.registers.pc).map_err(|_| format!("pc {} is out of range", self.registers.pc))?;
+		Ok(self.instructions.get(pc))
+	}
+
+	/// Executes the instruction at the current pc, unless the program has already stopped.
+	fn step(&mut self) -> Result<Status, super::Error> {
+		if self.status != Status::Running {
+			return Ok(self.status);
+		}
+
+		if self.limit.map_or(false, |limit| self.num_executed >= limit) {
+			self.status = Status::LimitReached;
+			return Ok(self.status);
+		}
+
+		let instruction =
+			if let Some(instruction) = self.current()? {
+				instruction
+			}
+			else {
+				self.status = Status::Finished;
+				return Ok(self.status);
+			};
+
+		let flow = (instruction.opcode.execute)(&mut self.registers, instruction.arg)?;
+		self.num_executed += 1;
+
+		match flow {
+			Flow::Next => self.registers.pc += 1,
+			Flow::Jump(offset) => self.registers.pc = self.registers.pc.checked_add(offset).ok_or("pc out of range")?,
+			Flow::Halt => self.status = Status::Halted,
+		}
+
+		Ok(self.status)
+	}
+
+	/// Executes instructions until the program stops.
+	fn run(&mut self) -> Result<Status, super::Error> {
+		loop {
+			let status = self.step()?;
+			if status != Status::Running {
+				return Ok(status);
+			}
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum Breakpoint {
+	/// Stop before executing the instruction at this pc.
+	Pc(i64),
+
+	/// Stop before executing any instruction with this opcode.
+	Opcode(&'static str),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct TraceEntry {
+	pc: i64,
+	instruction: Instruction,
+	acc_before: i64,
+	acc_after: i64,
+}
+
+impl std::fmt::Display for TraceEntry {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}: {} (acc {} -> {})", self.pc, self.instruction, self.acc_before, self.acc_after)
+	}
+}
+
+/// Why [`Debugger::continue_`] stopped.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Stop {
+	Breakpoint(Breakpoint),
+	AccChanged { old: i64, new: i64 },
+	Status(Status),
+}
+
+/// Wraps a [`Vm`] to record a trace of the instructions it executes and how many times each instruction was executed,
+/// and to stop it at breakpoints or when `acc` changes.
+#[derive(Debug)]
+struct Debugger<'a> {
+	vm: Vm<'a>,
+	opcodes: &'a OpcodeTable,
+	breakpoints: std::collections::BTreeSet<Breakpoint>,
+	watch_acc: bool,
+	trace: Vec<TraceEntry>,
+	execution_counts: Vec<usize>,
+}
+
+impl<'a> Debugger<'a> {
+	/// `opcodes` are the opcodes that breakpoints can be set on.
+	fn new(vm: Vm<'a>, opcodes: &'a OpcodeTable) -> Self {
+		let num_instructions = vm.instructions.len();
+
+		Debugger {
+			vm,
+			opcodes,
+			breakpoints: Default::default(),
+			watch_acc: false,
+			trace: vec![],
+			execution_counts: vec![0; num_instructions],
+		}
+	}
+
+	/// Executes one instruction, ignoring breakpoints and watches.
+	fn step(&mut self) -> Result<Status, super::Error> {
+		let pc = self.vm.registers.pc;
+		let acc_before = self.vm.registers.acc;
+		let instruction = self.vm.current()?.copied();
+		let num_executed = self.vm.num_executed;
+
+		let status = self.vm.step()?;
+
+		if let Some(instruction) = instruction {
+			if self.vm.num_executed > num_executed {
+				self.trace.push(TraceEntry { pc, instruction, acc_before, acc_after: self.vm.registers.acc });
+				self.execution_counts[usize::try_from(pc).expect("pc of an existing instruction fits in usize")] += 1;
+			}
 		}
 
-		let instruction = {
-			let pc: usize = std::convert::TryInto::try_into(pc).map_err(|err| format!("pc out of range: {err}"))?;
-			instructions.get(pc).copied()
+		Ok(status)
+	}
+
+	/// Executes instructions until a breakpoint is reached, `acc` changes while it is being watched, or the program stops.
+	///
+	/// Always executes at least one instruction, so that continuing from a breakpoint does not immediately stop at the same breakpoint.
+	fn continue_(&mut self) -> Result<Stop, super::Error> {
+		loop {
+			let acc_before = self.vm.registers.acc;
+
+			let status = self.step()?;
+			if status != Status::Running {
+				return Ok(Stop::Status(status));
+			}
+
+			if self.watch_acc && self.vm.registers.acc != acc_before {
+				return Ok(Stop::AccChanged { old: acc_before, new: self.vm.registers.acc });
+			}
+
+			if let Some(breakpoint) = self.breakpoint_at_pc()? {
+				return Ok(Stop::Breakpoint(breakpoint));
+			}
+		}
+	}
+
+	fn breakpoint_at_pc(&self) -> Result<Option<Breakpoint>, super::Error> {
+		let pc = Breakpoint::Pc(self.vm.registers.pc);
+		if self.breakpoints.contains(&pc) {
+			return Ok(Some(pc));
+		}
+
+		if let Some(instruction) = self.vm.current()? {
+			let opcode = Breakpoint::Opcode(instruction.opcode.name);
+			if self.breakpoints.contains(&opcode) {
+				return Ok(Some(opcode));
+			}
+		}
+
+		Ok(None)
+	}
+
+	fn execution_count(&self, pc: usize) -> usize {
+		self.execution_counts.get(pc).copied().unwrap_or_default()
+	}
+
+	/// The pcs of the instructions grouped by the number of times they were executed.
+	fn pcs_by_execution_count(&self) -> std::collections::BTreeMap<usize, Vec<usize>> {
+		let mut result: std::collections::BTreeMap<_, Vec<_>> = Default::default();
+		for (pc, &count) in self.execution_counts.iter().enumerate() {
+			result.entry(count).or_default().push(pc);
+		}
+		result
+	}
+
+	/// Executes a debugger command and returns its output.
+	///
+	/// - `break <pc>` / `break <opcode>`, `delete <pc>` / `delete <opcode>`: set / delete a breakpoint
+	/// - `watch acc` / `unwatch acc`: start / stop stopping when `acc` changes
+	/// - `step`: execute one instruction and print it
+	/// - `continue`: execute instructions until the next stop, and print why it stopped
+	/// - `print pc` / `print acc`: print a register
+	/// - `trace`: print every instruction executed so far
+	/// - `counts`: print the pcs of the instructions grouped by the number of times they were executed
+	fn command(&mut self, command: &str) -> Result<String, super::Error> {
+		use std::fmt::Write;
+
+		let parse_breakpoint = |arg: &str| -> Result<Breakpoint, super::Error> {
+			if let Ok(pc) = arg.parse() {
+				Ok(Breakpoint::Pc(pc))
+			}
+			else {
+				Ok(Breakpoint::Opcode(self.opcodes.get(arg)?.name))
+			}
 		};
-		match instruction {
-			Some(Instruction::Jmp(arg)) => {
-				pc += arg;
-				continue;
+
+		let mut result = String::new();
+
+		let parts: Vec<_> = command.split_whitespace().collect();
+		match &parts[..] {
+			["break", arg] => {
+				self.breakpoints.insert(parse_breakpoint(arg)?);
+			},
+
+			["delete", arg] => {
+				self.breakpoints.remove(&parse_breakpoint(arg)?);
+			},
+
+			["watch", "acc"] => self.watch_acc = true,
+
+			["unwatch", "acc"] => self.watch_acc = false,
+
+			["step"] => {
+				let num_executed = self.trace.len();
+				let status = self.step()?;
+				if let Some(entry) = self.trace.get(num_executed) {
+					writeln!(result, "{entry}")?;
+				}
+				if status != Status::Running {
+					writeln!(result, "stopped: {}", Stop::Status(status))?;
+				}
 			},
 
-			Some(Instruction::Acc(arg)) => acc += arg,
+			["continue"] => writeln!(result, "stopped: {}", self.continue_()?)?,
+
+			["print", "pc"] => writeln!(result, "{}", self.vm.registers.pc)?,
+
+			["print", "acc"] => writeln!(result, "{}", self.vm.registers.acc)?,
+
+			["trace"] =>
+				for entry in &self.trace {
+					writeln!(result, "{entry}")?;
+				},
 
-			Some(Instruction::Nop(_)) => (),
+			["counts"] =>
+				for (count, pcs) in self.pcs_by_execution_count() {
+					let pcs: Vec<_> = pcs.into_iter().map(|pc| pc.to_string()).collect();
+					writeln!(result, "{count}: {}", pcs.join(", "))?;
+				},
 
-			None => return Ok(BootResult::Finished(acc)),
+			_ => return Err(format!("invalid command {command:?}").into()),
 		}
 
-		pc += 1;
+		Ok(result)
+	}
+}
+
+impl std::fmt::Display for Stop {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Stop::Breakpoint(Breakpoint::Pc(pc)) => write!(f, "breakpoint at pc {pc}"),
+			Stop::Breakpoint(Breakpoint::Opcode(opcode)) => write!(f, "breakpoint on {opcode}"),
+			Stop::AccChanged { old, new } => write!(f, "acc changed from {old} to {new}"),
+			Stop::Status(Status::Running) => f.write_str("program is running"),
+			Stop::Status(Status::Finished) => f.write_str("program finished"),
+			Stop::Status(Status::Halted) => f.write_str("program halted"),
+			Stop::Status(Status::LimitReached) => f.write_str("instruction limit reached"),
+		}
+	}
+}
+
+fn boot(instructions: &[Instruction]) -> Result<BootResult, super::Error> {
+	let mut visited = vec![false; instructions.len()];
+
+	let mut vm = Vm::new(instructions);
+
+	loop {
+		if let Some(visited) = usize::try_from(vm.registers.pc).ok().and_then(|pc| visited.get_mut(pc)) {
+			if std::mem::replace(visited, true) {
+				return Ok(BootResult::InfiniteLoop(vm.registers.acc));
+			}
+		}
+
+		match vm.step()? {
+			Status::Running => (),
+			Status::Finished | Status::Halted | Status::LimitReached => return Ok(BootResult::Finished),
+		}
 	}
 }
 
 #[derive(Clone, Copy, Debug)]
 enum BootResult {
 	InfiniteLoop(i64),
-	Finished(i64),
+	Finished,
+}
+
+/// The control-flow graph of a program.
+///
+/// Node `i` is the instruction at pc `i`, and one more node after the last instruction represents the program finishing or halting.
+#[derive(Debug)]
+struct Cfg {
+	graph: petgraph::graph::DiGraph<(), ()>,
+
+	/// Instructions that can jump to a negative pc, along with that pc, or `None` if computing it overflows.
+	out_of_range_jumps: Vec<(usize, Option<i64>)>,
+}
+
+impl Cfg {
+	fn new(instructions: &[Instruction]) -> Result<Self, super::Error> {
+		let num_instructions = instructions.len();
+
+		let mut graph = petgraph::graph::DiGraph::with_capacity(num_instructions + 1, num_instructions);
+		for _ in 0..=num_instructions {
+			graph.add_node(());
+		}
+		let end = petgraph::graph::NodeIndex::new(num_instructions);
+
+		let mut out_of_range_jumps = vec![];
+
+		for (pc, instruction) in instructions.iter().enumerate() {
+			let node_index = petgraph::graph::NodeIndex::new(pc);
+
+			if instruction.opcode.flows == Flows::Halt {
+				graph.update_edge(node_index, end, ());
+				continue;
+			}
+
+			let (jump, next) = instruction.successors(i64::try_from(pc)?);
+			if jump.is_none() && instruction.opcode.flows != Flows::Next {
+				out_of_range_jumps.push((pc, None));
+			}
+
+			for successor in [jump, next].into_iter().flatten() {
+				match usize::try_from(successor) {
+					Ok(successor) => { graph.update_edge(node_index, petgraph::graph::NodeIndex::new(successor.min(num_instructions)), ()); },
+					Err(_) => out_of_range_jumps.push((pc, Some(successor))),
+				}
+			}
+		}
+
+		Ok(Cfg { graph, out_of_range_jumps })
+	}
+
+	fn end(&self) -> petgraph::graph::NodeIndex {
+		petgraph::graph::NodeIndex::new(self.graph.node_count() - 1)
+	}
+
+	/// Whether each instruction can be executed by the program, and whether the program can finish or halt.
+	fn reachable(&self) -> Vec<bool> {
+		let mut result = vec![false; self.graph.node_count()];
+		let walker = petgraph::visit::Bfs::new(&self.graph, petgraph::graph::NodeIndex::new(0));
+		for node_index in petgraph::visit::Walker::iter(walker, &self.graph) {
+			result[node_index.index()] = true;
+		}
+		result
+	}
+
+	/// Whether the program can finish or halt after executing each instruction.
+	fn terminates(&self) -> Vec<bool> {
+		let mut result = vec![false; self.graph.node_count()];
+		let graph = petgraph::visit::Reversed(&self.graph);
+		let walker = petgraph::visit::Bfs::new(graph, self.end());
+		for node_index in petgraph::visit::Walker::iter(walker, graph) {
+			result[node_index.index()] = true;
+		}
+		result
+	}
+}
+
+/// The result of analyzing a program without running it.
+///
+/// Conditional jumps are assumed to be able to go either way, so a program with them that "can" terminate might still loop forever when run.
+#[derive(Debug)]
+struct Analysis {
+	/// The pcs of the instructions that the program can never execute.
+	unreachable: Vec<usize>,
+
+	/// Groups of instructions that the program can execute repeatedly, but that can never be left to make the program finish or halt.
+	infinite_loops: Vec<Vec<usize>>,
+
+	/// Reachable instructions that can jump to a negative pc, along with that pc, or `None` if computing it overflows.
+	out_of_range_jumps: Vec<(usize, Option<i64>)>,
+
+	/// Whether the program can finish or halt.
+	terminates: bool,
+
+	/// If the program cannot terminate, the instructions that could each be flipped between `jmp` and `nop` so that it can.
+	repairs: Vec<(usize, Instruction)>,
+}
+
+fn analyze(instructions: &[Instruction], opcodes: &OpcodeTable) -> Result<Analysis, super::Error> {
+	let cfg = Cfg::new(instructions)?;
+	let reachable = cfg.reachable();
+	let terminates = cfg.terminates();
+
+	let unreachable = (0..instructions.len()).filter(|&pc| !reachable[pc]).collect();
+
+	let mut infinite_loops: Vec<Vec<_>> =
+		petgraph::algo::tarjan_scc(&cfg.graph).into_iter()
+		.filter(|component| match component[..] {
+			[node_index] => cfg.graph.contains_edge(node_index, node_index),
+			_ => true,
+		})
+		.map(|component| {
+			let mut component: Vec<_> = component.into_iter().map(petgraph::graph::NodeIndex::index).collect();
+			component.sort_unstable();
+			component
+		})
+		.filter(|component| component.iter().all(|&pc| reachable[pc] && !terminates[pc]))
+		.collect();
+	infinite_loops.sort_unstable();
+
+	let out_of_range_jumps = cfg.out_of_range_jumps.iter().copied().filter(|&(pc, _)| reachable[pc]).collect();
+
+	let end = cfg.end().index();
+	let terminates = reachable[end];
+
+	let mut repairs = vec![];
+	if !terminates {
+		let jmp = opcodes.get("jmp")?;
+		let nop = opcodes.get("nop")?;
+
+		let mut instructions = instructions.to_owned();
+
+		for pc in 0..instructions.len() {
+			// Flipping an instruction that the program does not execute does not change what it does.
+			if !reachable[pc] {
+				continue;
+			}
+
+			let original = instructions[pc];
+			let flipped_opcode = match original.opcode.name {
+				"jmp" => nop,
+				"nop" => jmp,
+				_ => continue,
+			};
+			let flipped = Instruction { opcode: flipped_opcode, arg: original.arg };
+
+			instructions[pc] = flipped;
+			if Cfg::new(&instructions)?.reachable()[end] {
+				repairs.push((pc, flipped));
+			}
+			instructions[pc] = original;
+		}
+	}
+
+	Ok(Analysis {
+		unreachable,
+		infinite_loops,
+		out_of_range_jumps,
+		terminates,
+		repairs,
+	})
 }
 
 #[cfg(test)]
@@ -131,15 +819,175 @@ acc +6\
 
 	#[test]
 	fn part1() {
-		let instructions = super::parse_program(INPUT.split('\n').map(|line| Ok(line.parse()?))).unwrap();
+		let opcodes = super::OpcodeTable::standard();
+		let instructions = super::parse_program(INPUT.split('\n').map(Ok), &opcodes).unwrap();
 
 		assert_eq!(super::part1(&instructions).unwrap(), 5);
 	}
 
 	#[test]
 	fn part2() {
-		let instructions = super::parse_program(INPUT.split('\n').map(|line| Ok(line.parse()?))).unwrap();
+		let opcodes = super::OpcodeTable::standard();
+		let instructions = super::parse_program(INPUT.split('\n').map(Ok), &opcodes).unwrap();
+
+		assert_eq!(super::part2(&instructions, &opcodes).unwrap(), 8);
+		assert_eq!(super::repair(&instructions, &opcodes).unwrap(), (7, 8));
+
+		let instructions = super::parse_program(["nop +2", "jmp +0", "acc +1"].iter().map(Ok), &opcodes).unwrap();
+		assert_eq!(super::repair(&instructions, &opcodes).unwrap(), (0, 1));
+
+		let instructions = super::parse_program(["acc +1", "jmp -1", "acc +2"].iter().map(Ok), &opcodes).unwrap();
+		assert_eq!(super::repair(&instructions, &opcodes).unwrap(), (1, 3));
+
+		let instructions = super::parse_program(["acc +1", "jmp -1", "jmp -3"].iter().map(Ok), &opcodes).unwrap();
+		assert!(super::repair(&instructions, &opcodes).is_err());
+
+		let instructions = super::parse_program(["acc +1", "nop +0"].iter().map(Ok), &opcodes).unwrap();
+		assert!(super::repair(&instructions, &opcodes).is_err());
+
+		// A jump whose target overflows fails instead of terminating, so it's neither a repair nor a way out of a loop.
+		let instructions = super::parse_program(["acc +1", "nop +9223372036854775807", "jmp -1"].iter().map(Ok), &opcodes).unwrap();
+		assert_eq!(super::repair(&instructions, &opcodes).unwrap(), (2, 1));
+
+		let instructions = super::parse_program(["jmp +2", "jmp +9223372036854775807", "jmp -1"].iter().map(Ok), &opcodes).unwrap();
+		assert_eq!(super::repair(&instructions, &opcodes).unwrap(), (2, 0));
+	}
+
+	#[test]
+	fn vm() {
+		// Computes 3^4 into acc, then jumps over the `acc +100` and halts before the `acc +1000`
+		const INPUT: &str = "\
+acc +1
+mul +3
+mul +3
+mul +3
+mul +3
+jnz +2
+acc +100
+hlt
+acc +1000\
+";
+
+		assert!(super::parse_program(INPUT.split('\n').map(Ok), &super::OpcodeTable::standard()).is_err());
+
+		let opcodes = super::OpcodeTable::extended();
+		let instructions = super::parse_program(INPUT.split('\n').map(Ok), &opcodes).unwrap();
+
+		let mut vm = super::Vm::new(&instructions);
+		assert_eq!(vm.step().unwrap(), super::Status::Running);
+		assert_eq!(vm.registers, super::Registers { pc: 1, acc: 1 });
+		assert_eq!(vm.run().unwrap(), super::Status::Halted);
+		assert_eq!(vm.registers, super::Registers { pc: 7, acc: 81 });
+		assert_eq!(vm.num_executed, 7);
+		assert_eq!(vm.step().unwrap(), super::Status::Halted);
+
+		let mut vm = super::Vm::new(&instructions).with_limit(3);
+		assert_eq!(vm.run().unwrap(), super::Status::LimitReached);
+		assert_eq!(vm.registers, super::Registers { pc: 3, acc: 9 });
+
+		let instructions = super::parse_program(["jz -1"].iter().map(Ok), &opcodes).unwrap();
+		let mut vm = super::Vm::new(&instructions);
+		assert!(vm.run().is_err());
+
+		let instructions = super::parse_program(["acc +5", "jz -1"].iter().map(Ok), &opcodes).unwrap();
+		let mut vm = super::Vm::new(&instructions);
+		assert_eq!(vm.run().unwrap(), super::Status::Finished);
+		assert_eq!(vm.registers, super::Registers { pc: 2, acc: 5 });
+	}
+
+	#[test]
+	fn debugger() {
+		let opcodes = super::OpcodeTable::standard();
+		let instructions = super::parse_program(INPUT.split('\n').map(Ok), &opcodes).unwrap();
+
+		let mut debugger = super::Debugger::new(super::Vm::new(&instructions).with_limit(10), &opcodes);
+
+		assert_eq!(debugger.command("step").unwrap(), "0: nop +0 (acc 0 -> 0)\n");
+		assert_eq!(debugger.command("break 4").unwrap(), "");
+		assert_eq!(debugger.command("continue").unwrap(), "stopped: breakpoint at pc 4\n");
+		assert_eq!(debugger.command("print acc").unwrap(), "5\n");
+		assert_eq!(debugger.command("delete 4").unwrap(), "");
+		assert_eq!(debugger.command("break acc").unwrap(), "");
+		assert_eq!(debugger.command("continue").unwrap(), "stopped: breakpoint on acc\n");
+		assert_eq!(debugger.command("print pc").unwrap(), "1\n");
+		assert_eq!(debugger.command("delete acc").unwrap(), "");
+		assert_eq!(debugger.command("watch acc").unwrap(), "");
+		assert_eq!(debugger.command("continue").unwrap(), "stopped: acc changed from 5 to 6\n");
+		assert_eq!(debugger.command("unwatch acc").unwrap(), "");
+		assert_eq!(debugger.command("continue").unwrap(), "stopped: instruction limit reached\n");
+
+		assert_eq!(debugger.command("trace").unwrap(), "\
+0: nop +0 (acc 0 -> 0)
+1: acc +1 (acc 0 -> 1)
+2: jmp +4 (acc 1 -> 1)
+6: acc +1 (acc 1 -> 2)
+7: jmp -4 (acc 2 -> 2)
+3: acc +3 (acc 2 -> 5)
+4: jmp -3 (acc 5 -> 5)
+1: acc +1 (acc 5 -> 6)
+2: jmp +4 (acc 6 -> 6)
+6: acc +1 (acc 6 -> 7)
+");
+
+		assert_eq!(debugger.command("counts").unwrap(), "\
+0: 5, 8
+1: 0, 3, 4, 7
+2: 1, 2, 6
+");
+		assert_eq!(debugger.execution_count(6), 2);
+
+		assert!(debugger.command("break mul").is_err());
+
+		// Breakpoints can be set on any opcode in the table, even ones that the program doesn't use.
+		let opcodes = super::OpcodeTable::extended();
+		let mut debugger = super::Debugger::new(super::Vm::new(&instructions), &opcodes);
+		assert_eq!(debugger.command("break mul").unwrap(), "");
+		assert_eq!(debugger.command("delete mul").unwrap(), "");
+		assert!(debugger.command("break div").is_err());
+		assert!(debugger.command("jump 3").is_err());
+
+		// Only instructions executed by the debugger itself are traced.
+		let mut vm = super::Vm::new(&instructions).with_limit(2);
+		assert_eq!(vm.run().unwrap(), super::Status::LimitReached);
+		let mut debugger = super::Debugger::new(vm, &opcodes);
+		assert_eq!(debugger.command("step").unwrap(), "stopped: instruction limit reached\n");
+		assert_eq!(debugger.command("trace").unwrap(), "");
+		assert_eq!(debugger.execution_count(2), 0);
+	}
+
+	#[test]
+	fn analyze() {
+		let opcodes = super::OpcodeTable::standard();
+		let instructions = super::parse_program(INPUT.split('\n').map(Ok), &opcodes).unwrap();
+
+		let analysis = super::analyze(&instructions, &opcodes).unwrap();
+		assert_eq!(analysis.unreachable, [5, 8]);
+		assert_eq!(analysis.infinite_loops, [vec![1, 2, 3, 4, 6, 7]]);
+		assert!(analysis.out_of_range_jumps.is_empty());
+		assert!(!analysis.terminates);
+		let repairs: Vec<_> = analysis.repairs.iter().map(|(pc, instruction)| (*pc, instruction.to_string())).collect();
+		assert_eq!(repairs, [(7, "nop -4".to_owned())]);
+
+		// The instruction at 1 would loop forever, but the program never reaches it and instead jumps to -1.
+		let instructions = super::parse_program(["jmp +2", "jmp +0", "nop +0", "jmp -4", "acc +1"].iter().map(Ok), &opcodes).unwrap();
+		let analysis = super::analyze(&instructions, &opcodes).unwrap();
+		assert_eq!(analysis.unreachable, [1, 4]);
+		assert!(analysis.infinite_loops.is_empty());
+		assert_eq!(analysis.out_of_range_jumps, [(3, Some(-1))]);
+		assert!(!analysis.terminates);
+		let repairs: Vec<_> = analysis.repairs.iter().map(|(pc, instruction)| (*pc, instruction.to_string())).collect();
+		assert_eq!(repairs, [(3, "nop -4".to_owned())]);
+
+		let mut vm = super::Vm::new(&instructions);
+		assert_eq!(vm.run().unwrap_err().0.to_string(), "pc -1 is out of range");
 
-		assert_eq!(super::part2(&instructions).unwrap(), 8);
+		// Conditional jumps can go either way, so the loop can be left.
+		let opcodes = super::OpcodeTable::extended();
+		let instructions = super::parse_program(["acc +3", "acc -1", "jnz -1", "jmp +2", "jmp -1", "hlt"].iter().map(Ok), &opcodes).unwrap();
+		let analysis = super::analyze(&instructions, &opcodes).unwrap();
+		assert_eq!(analysis.unreachable, [4]);
+		assert!(analysis.infinite_loops.is_empty());
+		assert!(analysis.terminates);
+		assert!(analysis.repairs.is_empty());
 	}
 }
@@ -6,11 +6,14 @@ pub(super) fn run() -> Result<(), super::Error> {
 
 		assert_eq!(result, 530627549);
 
+		let invalid_numbers = invalid_numbers(super::read_input_lines("day9")?, 25)?;
+		assert_eq!(invalid_numbers.first().map(|&(_, num)| num), Some(result));
+
 		result
 	};
 
 	{
-		let result = part2(super::read_input_lines("day9")?, part1_result)?;
+		let result = part2(super::read_input_lines("day9")?, part1_result.try_into()?)?;
 
 		println!("9b: {result}");
 
@@ -20,60 +23,138 @@ pub(super) fn run() -> Result<(), super::Error> {
 	Ok(())
 }
 
-fn part1(input: impl Iterator<Item = Result<u64, super::Error>>, num_summands: usize) -> Result<u64, super::Error> {
-	let mut nums = std::collections::VecDeque::with_capacity(num_summands);
+fn part1(input: impl Iterator<Item = Result<u64, super::Error>>, window_size: usize) -> Result<u64, super::Error> {
+	let mut validator = Validator::new(window_size);
 
 	for num in input {
 		let num = num?;
-
-		if nums.len() == num_summands {
-			let is_valid =
-				nums.iter().copied().rev().take(num_summands).enumerate().any(|(i, num1)|
-					nums.iter().copied().rev().take(num_summands).enumerate().any(|(j, num2)|
-						i != j && num1 + num2 == num));
-			if !is_valid {
-				return Ok(num);
-			}
-
-			let _ = nums.pop_front();
+		if validator.push(num) == Some(false) {
+			return Ok(num);
 		}
-
-		nums.push_back(num);
 	}
 
 	Err("no solution".into())
 }
 
-fn part2(input: impl Iterator<Item = Result<u64, super::Error>>, expected_sum: u64) -> Result<u64, super::Error> {
-	let mut range: std::collections::VecDeque<_> = Default::default();
-	let mut sum = 0;
+/// All the numbers that are not the sum of two different numbers among the `window_size` numbers before them, along with their indices.
+fn invalid_numbers(input: impl Iterator<Item = Result<u64, super::Error>>, window_size: usize) -> Result<Vec<(usize, u64)>, super::Error> {
+	let mut validator = Validator::new(window_size);
+	let mut result = vec![];
 
-	for num in input {
+	for (i, num) in input.enumerate() {
 		let num = num?;
+		if validator.push(num) == Some(false) {
+			result.push((i, num));
+		}
+	}
+
+	Ok(result)
+}
+
+/// Checks each number against a sliding window of the numbers before it.
+///
+/// The window is also kept sorted, so that checking a number and sliding the window are both O(window size)
+/// instead of checking every pair of numbers in the window.
+#[derive(Debug)]
+struct Validator {
+	window_size: usize,
+	window: std::collections::VecDeque<u64>, // Much faster than the pairwise check for large windows; see `tests::bench_part1`
+	sorted: Vec<u64>,
+}
 
-		range.push_back(num);
-		sum += num;
+impl Validator {
+	fn new(window_size: usize) -> Self {
+		Validator {
+			window_size,
+			window: std::collections::VecDeque::with_capacity(window_size),
+			sorted: Vec::with_capacity(window_size),
+		}
+	}
 
-		while sum > expected_sum {
-			if let Some(first_num) = range.pop_front() {
-				sum -= first_num;
+	/// Checks whether the given number is valid, and then slides it into the window.
+	///
+	/// Returns `None` if the window is not full yet.
+	fn push(&mut self, num: u64) -> Option<bool> {
+		let result = (self.window.len() == self.window_size).then(|| self.is_valid(num));
+
+		if result.is_some() {
+			if let Some(oldest) = self.window.pop_front() {
+				let index = self.sorted.binary_search(&oldest).expect("every number in the window is also in the sorted window");
+				let _ = self.sorted.remove(index);
 			}
 		}
 
-		if sum == expected_sum {
-			let (min, max) =
-				range.iter()
-				.copied()
-				.fold(None, |min_max, num| match (min_max, num) {
-					(Some((min, max)), num) => Some((std::cmp::min(min, num), std::cmp::max(max, num))),
-					(None, num) => Some((num, num)),
-				})
-				.ok_or("no solution")?;
-			return Ok(min + max);
+		if self.window_size > 0 {
+			self.window.push_back(num);
+			let index = self.sorted.binary_search(&num).unwrap_or_else(|index| index);
+			self.sorted.insert(index, num);
 		}
+
+		result
 	}
 
-	Err("no solution".into())
+	/// Whether the given number is the sum of two numbers in the window with different values.
+	fn is_valid(&self, num: u64) -> bool {
+		if self.sorted.is_empty() {
+			return false;
+		}
+
+		let (mut lo, mut hi) = (0, self.sorted.len() - 1);
+		while lo < hi {
+			let (num1, num2) = (self.sorted[lo], self.sorted[hi]);
+			match num1.checked_add(num2).map_or(std::cmp::Ordering::Greater, |sum| sum.cmp(&num)) {
+				std::cmp::Ordering::Less => lo += 1,
+				std::cmp::Ordering::Greater => hi -= 1,
+				// Every number between lo and hi is the same as num1 and num2, so there is no pair with different values left.
+				std::cmp::Ordering::Equal => return num1 != num2,
+			}
+		}
+
+		false
+	}
+}
+
+fn part2(input: impl Iterator<Item = Result<i64, super::Error>>, expected_sum: i64) -> Result<i64, super::Error> {
+	let nums: Vec<_> = input.collect::<Result<_, _>>()?;
+
+	let range = ranges_with_sum(&nums, expected_sum, 2).into_iter().next().ok_or("no solution")?;
+	let range = &nums[range];
+	let min = range.iter().min().ok_or("no solution")?;
+	let max = range.iter().max().ok_or("no solution")?;
+	Ok(min + max)
+}
+
+/// All the ranges of at least `min_len` (and at least one) contiguous numbers that sum to `expected_sum`,
+/// ordered by where they end and then by where they start.
+///
+/// The numbers can be negative, so this can't slide a window over them. Instead, a range sums to `expected_sum`
+/// if the prefix sum before its start is its end's prefix sum minus `expected_sum`, so it looks up the starts by prefix sum.
+fn ranges_with_sum(nums: &[i64], expected_sum: i64, min_len: usize) -> Vec<std::ops::Range<usize>> {
+	let min_len = min_len.max(1);
+
+	let prefix_sums: Vec<i128> =
+		std::iter::once(0)
+		.chain(nums.iter().scan(0, |sum, &num| {
+			*sum += i128::from(num);
+			Some(*sum)
+		}))
+		.collect();
+
+	let mut starts_by_prefix_sum: std::collections::HashMap<i128, Vec<usize>> = Default::default();
+
+	let mut result = vec![];
+
+	for end in min_len..prefix_sums.len() {
+		// Only the starts that make the range long enough are added, as the end moves past them.
+		let start = end - min_len;
+		starts_by_prefix_sum.entry(prefix_sums[start]).or_default().push(start);
+
+		if let Some(starts) = starts_by_prefix_sum.get(&(prefix_sums[end] - i128::from(expected_sum))) {
+			result.extend(starts.iter().map(|&start| start..end));
+		}
+	}
+
+	result
 }
 
 #[cfg(test)]
@@ -110,4 +191,103 @@ mod tests {
 	fn part2() {
 		assert_eq!(super::part2(INPUT.split('\n').map(|line| Ok(line.parse()?)), 127).unwrap(), 62);
 	}
+
+	#[test]
+	fn invalid_numbers() {
+		assert_eq!(super::invalid_numbers(INPUT.split('\n').map(|line| Ok(line.parse()?)), 5).unwrap(), [(14, 127)]);
+
+		// The two numbers must have different values, not just be at different positions.
+		assert_eq!(super::invalid_numbers([1, 2, 3, 5, 5, 10, 7, 6].iter().copied().map(Ok), 5).unwrap(), [(5, 10), (7, 6)]);
+
+		assert_eq!(super::invalid_numbers([1, 2, 3].iter().copied().map(Ok), 0).unwrap(), [(0, 1), (1, 2), (2, 3)]);
+
+		assert_eq!(super::invalid_numbers([u64::MAX, u64::MAX, 0, u64::MAX].iter().copied().map(Ok), 3).unwrap(), []);
+	}
+
+	#[test]
+	#[ignore = "benchmark"]
+	fn bench_part1() {
+		// `part1` before it was changed to use `Validator`.
+		fn nested_scan(input: &[u64], window_size: usize) -> Vec<(usize, u64)> {
+			let mut result = vec![];
+
+			let mut nums: std::collections::VecDeque<u64> = std::collections::VecDeque::with_capacity(window_size);
+
+			for (i, &num) in input.iter().enumerate() {
+				if nums.len() == window_size {
+					let is_valid =
+						nums.iter().copied().enumerate().any(|(i, num1)|
+							nums.iter().copied().enumerate().any(|(j, num2)|
+								i != j && num1 != num2 && num1.checked_add(num2) == Some(num)));
+					if !is_valid {
+						result.push((i, num));
+					}
+
+					let _ = nums.pop_front();
+				}
+
+				nums.push_back(num);
+			}
+
+			result
+		}
+
+		const NUM_CHECKED: usize = 1_000;
+
+		for &window_size in &[25, 250, 2_500] {
+			let mut random = super::super::random_numbers();
+
+			let mut input: Vec<u64> = (0..window_size).map(|_| random() % 1_000_000_000).collect();
+			for _ in 0..NUM_CHECKED {
+				// Half valid numbers, since those are the ones where the nested scan can stop early.
+				// They are only made from numbers below 1_000_000_000 so that they don't keep growing.
+				let num =
+					if random() % 2 == 0 {
+						random() % 1_000_000_000
+					}
+					else {
+						let window = &input[(input.len() - window_size)..];
+						let mut random_small_num = || loop {
+							#[allow(clippy::cast_possible_truncation)]
+							let num = window[(random() % window_size as u64) as usize];
+							if num < 1_000_000_000 {
+								break num;
+							}
+						};
+						random_small_num() + random_small_num()
+					};
+				input.push(num);
+			}
+
+			let start = std::time::Instant::now();
+			let expected = nested_scan(&input, window_size);
+			let nested_scan_elapsed = start.elapsed();
+
+			let start = std::time::Instant::now();
+			let actual = super::invalid_numbers(input.iter().copied().map(Ok), window_size).unwrap();
+			let validator_elapsed = start.elapsed();
+
+			assert_eq!(actual, expected);
+
+			println!("window size {window_size}: nested scan: {nested_scan_elapsed:?}, validator: {validator_elapsed:?}");
+		}
+	}
+
+	#[test]
+	fn ranges_with_sum() {
+		let nums: Vec<i64> = INPUT.split('\n').map(|line| line.parse().unwrap()).collect();
+
+		assert_eq!(super::ranges_with_sum(&nums, 127, 2), [2..6]);
+
+		// A single number is a range too, if the minimum length allows it.
+		assert_eq!(super::ranges_with_sum(&nums, 127, 1), [2..6, 14..15]);
+
+		assert_eq!(super::ranges_with_sum(&nums, 35, 1), [0..1, 1..3]);
+
+		assert_eq!(super::ranges_with_sum(&[3, -2, 2, -3, 3, 0], 0, 2), [1..3, 0..4, 1..5, 3..5, 1..6, 3..6]);
+
+		assert_eq!(super::ranges_with_sum(&[i64::MAX, i64::MAX, i64::MIN, i64::MIN], -2, 2), [0..4]);
+
+		assert!(super::ranges_with_sum(&[5], 5, 2).is_empty());
+	}
 }
@@ -74,6 +74,33 @@ fn read_input_lines<T>(filename: &str) -> Result<impl Iterator<Item = Result<T,
 	Ok(Lines::new(inner))
 }
 
+fn push_json_string(result: &mut String, s: &str) {
+	use std::fmt::Write;
+
+	result.push('"');
+	for c in s.chars() {
+		match c {
+			'"' => result.push_str("\\\""),
+			'\\' => result.push_str("\\\\"),
+			c if c < ' ' => write!(result, "\\u{:04x}", u32::from(c)).expect("cannot fail to write to String"),
+			c => result.push(c),
+		}
+	}
+	result.push('"');
+}
+
+/// A deterministic xorshift generator of pseudo-random numbers, for generating benchmark inputs.
+#[cfg(test)]
+fn random_numbers() -> impl FnMut() -> u64 {
+	let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+	move || {
+		state ^= state << 13;
+		state ^= state >> 7;
+		state ^= state << 17;
+		state
+	}
+}
+
 struct Error(Box<dyn std::error::Error>, backtrace::Backtrace);
 
 impl std::fmt::Debug for Error {